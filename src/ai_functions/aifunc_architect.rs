@@ -35,6 +35,7 @@ pub fn print_site_urls(_project_description: &str) {
     /// Input: Takes in a project description of a website build
     /// Function: Outputs a list of external public API endpoints that should be used in the building of the website
     /// Important: Only selects url endpoint(s) which do not require any API Keys at all
+    /// Important: KNOWN_WORKING_URLS, when given, answered in earlier projects and are preferred when they fit
     /// Output: Prints a list response of external urls in the following format:
     /// ["url1", "url2", "url3", ...]
     /// Example:
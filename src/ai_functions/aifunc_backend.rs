@@ -5,13 +5,21 @@ pub fn print_backend_webserver_code(_project_description_and_template: &str) {
     /// INPUT: Takes in a PROJECT_DESCRIPTION and CODE_TEMPLATE for a website backend build
     /// IMPORTANT: The backend code is ONLY an example. If the Project Description requires it, make as many changes as you like, most of the time you will need to write new codes, functions or modules ... which is encouraged.
     /// IMPORTANT: You do not need to follow the backend code exactly. Write functions that make sense for the users request if required.
-    /// FUNCTION: 
-    /// Takes an existing set of code marked as CODE_TEMPLATE and updates or re-writes it to work for the purpose in the PROJECT_DESCRIPTION. 
-    /// Write new codes/functions if new features are required. 
+    /// FUNCTION:
+    /// Takes an existing set of code marked as CODE_TEMPLATE and updates or re-writes it to work for the purpose in the PROJECT_DESCRIPTION.
+    /// Write new codes/functions if new features are required.
     /// If needed, data must be fetched from the internet using OPTIONAL_EXTERNAL_URLS and store in the database BEFORE webserver starting.    
     /// The Webserver should have endpoints all CRUD operations.
+    /// IMPORTANT: If a PERSISTENCE_MODULE is given, it is already saved as "src/db.rs". Declare it with "mod db;"
+    /// and store ALL data through its functions (open the connection once with db::open("data/app.db") and share it
+    /// between handlers inside a std::sync::Mutex).
+    /// Do not copy its code into main.rs and do not keep the data in memory or in JSON files.
+    /// IMPORTANT: If an AUTH_MODULE is given, it is already saved as "src/auth.rs". Declare it with "mod auth;", register
+    /// it with ".app_data(auth_state.clone()).configure(auth::configure)" and protect every user specific route with the
+    /// auth::AuthenticatedUser extractor or the auth::require_login middleware. Never write your own login or logout code.
     /// IMPORTANT: The following libraries are already installed
-    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors
+    ///   reqwest, serde, serde_json, tokio, actix-web, async-trait, actix_cors, rusqlite (only with a PERSISTENCE_MODULE),
+    ///   argon2 and rand_core (only with an AUTH_MODULE)
     /// No other external libraries should be used. Write functions that fit with the description from the PROJECT_DESCRIPTION
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
     println!(OUTPUT)
@@ -26,6 +34,7 @@ pub fn print_improved_webserver_code(_project_description_and_template: &str) {
     ///   3. ONLY writes the code. No commentary.
     /// IMPORTANT: The following libraries are already installed. Does not use ANY libraries other than what was provided in the template
     ///   reqwest, serde, serde_json, tokio, actix-web, async-trait
+    /// IMPORTANT: "mod db;" (rusqlite) and "mod auth;" (argon2, rand_core) are provided modules. Keep using them, never rewrite them.
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
     println!(OUTPUT)
 }
@@ -34,6 +43,7 @@ pub fn print_improved_webserver_code(_project_description_and_template: &str) {
 pub fn print_fixed_code(_broken_code_with_bugs: &str) {
     /// INPUT: Takes in Rust BROKEN_CODE and the ERROR_BUGS found
     /// FUNCTION: Removes bugs from code
+    /// IMPORTANT: KNOWN_FIXES, when given, are fixes of similar errors which worked in earlier projects
     /// IMPORTANT: Only prints out the new and improved code. No commentary or anything else
     /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
     println!(OUTPUT)
@@ -46,6 +56,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     /// LOGIC: Script analyses all code and can categorize into the following object keys:
     ///   "route": This represents the url path of the endpoint
     ///   "is_route_dynamic": if a route has curly braces in it such as {symbol} or {id} as an example, then this will be set to true
+    ///   "is_route_protected": true if the handler takes an auth::AuthenticatedUser argument or the route is wrapped with auth::require_login
     ///   "method": This represents the method being called
     ///   "request_body": This represents the body of a post method request
     ///   "response": This represents the output based upon the structs in the code and understanding the functions
@@ -79,6 +90,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   {
     ///     "route": "/item/{id}",
     ///     "is_route_dynamic": "true",
+    ///     "is_route_protected": "false",
     ///     "method": "get"
     ///     "request_body": "None",
     ///     "response": {
@@ -90,6 +102,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   {
     ///     "route": "/item",
     ///     "is_route_dynamic": "false",
+    ///     "is_route_protected": "false",
     ///     "method": "post",
     ///     "request_body": {
     ///       "id": "number",
@@ -101,6 +114,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   {
     ///     "route": "/item/{id}",
     ///     "is_route_dynamic": "true",
+    ///     "is_route_protected": "false",
     ///     "method": "delete",
     ///     "request_body": "None",
     ///     "response": "None"
@@ -108,6 +122,7 @@ pub fn print_rest_api_endpoints(_code_input: &str) {
     ///   {
     ///     "route": "/crypto",
     ///     "is_route_dynamic": "false",
+    ///     "is_route_protected": "false",
     ///     "method": "get",
     ///     "request_body": "None",
     ///     "response": "not_provided"
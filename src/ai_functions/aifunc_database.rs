@@ -0,0 +1,72 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_database_entities(_project_description: &str) {
+    /// Input: Takes in a project description of a website build which requires CRUD functionality
+    /// Function: Designs the database entities (tables) needed to persist the data of the website
+    /// Important: Every entity must have exactly one primary key column named "id" of type "INTEGER"
+    /// Important: data_type must be one of the SQLite types: "INTEGER", "REAL", "TEXT", "BLOB"
+    /// Important: Entity and column names are snake_case. Entity names are plural.
+    /// Output: Prints a JSON list of entities in the following format:
+    /// [
+    ///   {
+    ///     "name": "todo_items",
+    ///     "columns": [
+    ///       { "name": "id", "data_type": "INTEGER", "is_primary_key": true, "is_nullable": false },
+    ///       { "name": "title", "data_type": "TEXT", "is_primary_key": false, "is_nullable": false },
+    ///       { "name": "completed", "data_type": "INTEGER", "is_primary_key": false, "is_nullable": false }
+    ///     ]
+    ///   }
+    /// ]
+    /// Example:
+    ///   project_description = "build a website that lets users keep a list of their favourite crypto coins"
+    ///   prints:
+    /// [
+    ///   {
+    ///     "name": "favourite_coins",
+    ///     "columns": [
+    ///       { "name": "id", "data_type": "INTEGER", "is_primary_key": true, "is_nullable": false },
+    ///       { "name": "symbol", "data_type": "TEXT", "is_primary_key": false, "is_nullable": false },
+    ///       { "name": "added_at", "data_type": "TEXT", "is_primary_key": false, "is_nullable": true }
+    ///     ]
+    ///   }
+    /// ]
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_sqlite_migrations(_database_entities: &str) {
+    /// INPUT: Takes in DATABASE_ENTITIES as a JSON list of tables with their columns
+    /// FUNCTION: Writes the SQLite migration that creates every entity as a table
+    /// IMPORTANT: Use "CREATE TABLE IF NOT EXISTS" so that the migration can be applied more than once
+    /// IMPORTANT: The "id" column is declared as "INTEGER PRIMARY KEY AUTOINCREMENT"
+    /// IMPORTANT: Only SQLite syntax. No comments, no transactions, no other statements than CREATE TABLE / CREATE INDEX
+    /// OUTPUT: Print ONLY the SQL, nothing else. This function ONLY prints SQL.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_fixed_migrations(_broken_migrations_with_errors: &str) {
+    /// INPUT: Takes in SQLite BROKEN_MIGRATIONS, the DATABASE_ENTITIES they must create and the ERROR_BUGS found when applying them
+    /// FUNCTION: Removes the errors from the migrations so that every entity is created with all of its columns
+    /// IMPORTANT: Only prints out the fixed SQL. No commentary or anything else
+    /// OUTPUT: Print ONLY the SQL, nothing else. This function ONLY prints SQL.
+    println!(OUTPUT)
+}
+
+#[ai_function]
+pub fn print_persistence_module(_entities_and_migrations: &str) {
+    /// INPUT: Takes in DATABASE_ENTITIES as a JSON list of tables and the MIGRATIONS_PATH of the SQLite migration file
+    /// FUNCTION: Writes the Rust module "db.rs" which is the ONLY persistence layer of an actix-web backend
+    /// LOGIC: The module must contain:
+    ///   1. A struct per entity deriving serde Serialize, Deserialize, Clone, Debug with one field per column
+    ///   2. "pub fn open(path: &str) -> rusqlite::Result<rusqlite::Connection>" which creates the parent directory
+    ///      of the path if missing, opens the database file and applies the migration with: conn.execute_batch(include_str!(MIGRATIONS_PATH))
+    ///   3. For every entity: create, read by id, read all, update and delete functions taking "&rusqlite::Connection"
+    ///      and returning "rusqlite::Result<...>"
+    /// IMPORTANT: The following libraries are already installed. Does not use ANY other library
+    ///   rusqlite, serde, serde_json
+    /// IMPORTANT: The module does not contain a main function and does not start any server
+    /// OUTPUT: Print ONLY the code, nothing else. This function ONLY prints code.
+    println!(OUTPUT)
+}
@@ -4,6 +4,7 @@ use ai_functions::ai_function;
 pub fn convert_user_input_to_goal(_user_request: &str) {
     /// Input: Takes in a user request
     /// Function: Converts user request into a short summarized goal
+    /// Important: CLARIFICATIONS, when given, are the user's answers to follow-up questions and belong to the request
     /// Output: Prints goal. All outputs start with "build a website that ..."
     /// Example 1:
     ///   user_request = "I need a website that lets users login and logout. It needs to look fancy and accept payments."
@@ -13,3 +14,22 @@ pub fn convert_user_input_to_goal(_user_request: &str) {
     ///   OUTPUT = "build a website that fetches and stores crypto price data within a supabase setup including a frontend UI to fetch the data."
     println!(OUTPUT)
 }
+
+#[ai_function]
+pub fn print_clarifying_questions(_user_request: &str) {
+    /// Input: Takes in a user request to build a website
+    /// Function: Lists the questions a developer must ask before building the website, only for what the request leaves open:
+    ///   where the data comes from, whether users log in, what is stored and for how long, which pages are needed
+    /// Important: Prints an empty list when the request already answers them. Never more than MAX_QUESTIONS questions
+    /// Output: Prints a list of short questions in the following format:
+    /// ["question1", "question2", ...]
+    /// Example 1:
+    ///   user_request = "a website for crypto"
+    ///   prints:
+    /// ["Which crypto data should it show, e.g. prices or portfolios?", "Should users log in to save their coins?", "Do you need price history stored in a database?"]
+    /// Example 2:
+    ///   user_request = "a public TODO list api where anyone can add, list and delete items, stored in memory"
+    ///   prints:
+    /// []
+    println!(OUTPUT)
+}
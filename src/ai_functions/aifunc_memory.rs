@@ -0,0 +1,11 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn summarize_agent_memory(_conversation: &str) {
+    /// Input: Takes in the earlier turns of a conversation between an agent and the functions it called
+    /// Function: Summarizes what was asked, what was answered and which problems or errors came up
+    /// Important: Keeps every decision, error message and fix which later turns may depend on
+    /// Important: Drops code which is repeated word for word, only keeps what changed between attempts
+    /// Output: Prints the summary as plain text, in at most 20 lines
+    println!(OUTPUT)
+}
@@ -0,0 +1,24 @@
+use ai_functions::ai_function;
+
+#[ai_function]
+pub fn print_security_review(_code_input_and_static_findings: &str) {
+    /// INPUT: Takes in the Rust webserver CODE_INPUT generated by AI and the STATIC_FINDINGS already reported by a scanner
+    /// FUNCTION: Reviews the code as a security engineer before it is executed on the developer machine
+    /// LOGIC: Looks for anything that could harm the machine or the users of the website:
+    ///   spawning processes, reading or writing files outside the project, network calls to unexpected hosts,
+    ///   hardcoded secrets, request bodies without size limits, unsafe code, missing authentication on user data
+    /// IMPORTANT: Only reports NEW findings which are not already part of STATIC_FINDINGS
+    /// IMPORTANT: severity is one of "Low", "Medium", "High". "High" means the code must not be executed
+    /// IMPORTANT: Prints an empty list [] if there is nothing to report. No commentary or anything else.
+    /// Output: Prints a JSON list of findings in the following format:
+    /// [
+    ///   {
+    ///     "file": "src/main.rs",
+    ///     "line": 42,
+    ///     "severity": "High",
+    ///     "rule": "llm_review",
+    ///     "message": "Deletes files in the user home directory on startup"
+    ///   }
+    /// ]
+    println!(OUTPUT)
+}
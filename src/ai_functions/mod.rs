@@ -1,3 +1,6 @@
 pub mod aifunc_architect;
 pub mod aifunc_backend;
+pub mod aifunc_database;
 pub mod aifunc_managing;
+pub mod aifunc_memory;
+pub mod aifunc_security;
@@ -1,67 +1,445 @@
-use crate::models::general::llm::{APIResponse, ChatCompletion, Message};
+use crate::apis::response_cache::ResponseCache;
+use crate::models::general::config::AppConfig;
+use crate::models::general::llm::{
+    APIErrorResponse, APIStreamChunk, ChatCompletion, FunctionDefinition, Message, Tool,
+    ToolChoice, ToolChoiceFunction,
+};
 use dotenv::dotenv;
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Client;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Client, Proxy};
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{field, instrument, Span};
 
-//Call Large Language Model (i.e. GPT-4)
-pub async fn call_gpt(messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error + Send>> {
-    dotenv().ok();
-
-    let api_key =
-        env::var("OPEN_AI_KEY").expect("OPENAI_API_KEY not found in environment variables");
-    let api_org =
-        env::var("OPEN_AI_ORG_ID").expect("OPENAI_ORG not found in environment variables");
-
-    let url = "https://api.openai.com/v1/chat/completions";
-
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("Bearer {}", api_key))
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-    headers.insert(
-        "OpenAI-Organization",
-        HeaderValue::from_str(&api_org.as_str())
-            .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?,
-    );
-
-    let client = Client::builder()
-        .default_headers(headers)
-        .build()
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-
-    let chat_completion = ChatCompletion {
-        model: "gpt-4".to_string(),
-        messages,
-        temperature: 0.1,
-    };
-
-    //Troubleshooting
-    // let res_raw = client
-    //     .post(url)
-    //     .json(&chat_completion)
-    //     .send()
-    //     .await
-    //     .unwrap();
-    // dbg!(res_raw.text().await.unwrap());
-
-    let response: APIResponse = client
-        .post(url)
-        .json(&chat_completion)
-        .send()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?
-        .json()
-        .await
-        .map_err(|e| -> Box<dyn std::error::Error + Send> { Box::new(e) })?;
-    Ok(response.choices[0].message.content.clone())
+const LLM_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RESPONSE_CACHE_DIR: &str = "responses";
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const BASE_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+// Why a call to the LLM failed, and whether trying again can help
+#[derive(Debug)]
+pub enum LlmError {
+    // 429, waits Retry-After when the API sends it
+    RateLimited {
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    // 5xx and 408
+    Server {
+        status: u16,
+        message: String,
+    },
+    // Connection lost, timeout, stream cut in the middle
+    Network(String),
+    // No message in the response
+    EmptyResponse(String),
+    // Not the JSON asked for, e.g. cut short at max_tokens
+    InvalidResponse(String),
+    // Missing or rejected API key
+    Auth(String),
+    // Any other 4xx: the request itself is wrong, sending it again changes nothing
+    InvalidRequest {
+        status: u16,
+        message: String,
+    },
+    // Replay only run and the request was never recorded, holds the cache key
+    NotRecorded(String),
+}
+
+impl LlmError {
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            LlmError::RateLimited { .. }
+                | LlmError::Server { .. }
+                | LlmError::Network(_)
+                | LlmError::EmptyResponse(_)
+                | LlmError::InvalidResponse(_)
+        )
+    }
+
+    fn from_status(status: u16, retry_after: Option<Duration>, body: &str) -> Self {
+        let message = api_error_message(body);
+        match status {
+            401 | 403 => LlmError::Auth(message),
+            429 => LlmError::RateLimited {
+                retry_after,
+                message,
+            },
+            408 | 500..=599 => LlmError::Server { status, message },
+            _ => LlmError::InvalidRequest { status, message },
+        }
+    }
+}
+
+impl std::fmt::Display for LlmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LlmError::RateLimited { message, .. } => write!(f, "rate limited: {}", message),
+            LlmError::Server { status, message } => {
+                write!(f, "server error {}: {}", status, message)
+            }
+            LlmError::Network(message) => write!(f, "network error: {}", message),
+            LlmError::EmptyResponse(message) => write!(f, "empty response: {}", message),
+            LlmError::InvalidResponse(message) => write!(f, "invalid response: {}", message),
+            LlmError::Auth(message) => write!(f, "authentication failed: {}", message),
+            LlmError::InvalidRequest { status, message } => {
+                write!(f, "invalid request {}: {}", status, message)
+            }
+            LlmError::NotRecorded(key) => write!(f, "no recorded response for request {}", key),
+        }
+    }
+}
+
+impl std::error::Error for LlmError {}
+
+// The message of an OpenAI error body, or the body itself when it has another shape
+fn api_error_message(body: &str) -> String {
+    serde_json::from_str::<APIErrorResponse>(body)
+        .map(|error_response| error_response.error.message)
+        .unwrap_or_else(|_| body.trim().to_string())
+}
+
+// Retry-After is either a number of seconds or an HTTP date, only the first is used by OpenAI
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: BASE_RETRY_DELAY,
+            max_delay: MAX_RETRY_DELAY,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Wait before the next attempt, None when the error is fatal or the attempts are used up
+    // attempt starts at 1, jitter is between 0 and 1 and adds up to half of the exponential delay
+    pub fn delay(&self, attempt: u32, error: &LlmError, jitter: f64) -> Option<Duration> {
+        if !error.is_retryable() || attempt >= self.max_attempts {
+            return None;
+        }
+        if let LlmError::RateLimited {
+            retry_after: Some(retry_after),
+            ..
+        } = error
+        {
+            return Some((*retry_after).min(self.max_delay));
+        }
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1))
+            .min(self.max_delay);
+        Some(exponential.mul_f64(1.0 + jitter.clamp(0.0, 1.0) / 2.0))
+    }
+}
+
+// Cheap jitter, good enough to spread the retries of concurrent agents
+pub fn retry_jitter() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.subsec_nanos())
+        .unwrap_or_default();
+    f64::from(nanos % 1000) / 1000.0
+}
+
+// Where a model is served, named in lowercase in the config
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    #[default]
+    OpenAi,
+}
+
+impl LlmProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LlmProvider::OpenAi => "openai",
+        }
+    }
+
+    // Without it, structured outputs fall back to the printer prompt
+    pub fn supports_tool_calling(&self) -> bool {
+        match self {
+            LlmProvider::OpenAi => true,
+        }
+    }
+
+    fn chat_completions_url(&self) -> &'static str {
+        match self {
+            LlmProvider::OpenAi => "https://api.openai.com/v1/chat/completions",
+        }
+    }
+}
+
+// Model of one call, routed from the config by agent and ai_function
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSettings {
+    pub provider: LlmProvider,
+    pub model: String,
+    pub temperature: f32,
+    // None lets the model answer up to its context size
+    pub max_tokens: Option<u32>,
+    // Structured outputs are asked through tool calling when the provider supports it
+    pub is_structured_output: bool,
+    // Set for an agent retried after a failure, the cached response is the one which failed
+    pub is_cache_bypassed: bool,
+}
+
+impl ModelSettings {
+    pub fn uses_tool_calling(&self) -> bool {
+        self.is_structured_output && self.provider.supports_tool_calling()
+    }
+}
+
+// Answer given as the arguments of a forced function call, which follow the JSON Schema
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StructuredOutput {
+    pub name: String,
+    pub schema: serde_json::Value,
+}
+
+// Client of the chat completions API, built once and reused by every call
+pub struct LlmClient {
+    client: Client,
+    api_key: Option<String>,
+    api_org: Option<String>,
+    cache: Option<ResponseCache>,
+    is_replay_only: bool,
+}
+
+// The API key stays out of debug output
+impl std::fmt::Debug for LlmClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmClient")
+            .field("cache", &self.cache)
+            .field("is_replay_only", &self.is_replay_only)
+            .finish()
+    }
+}
+
+impl LlmClient {
+    // Credentials come from OPEN_AI_KEY and OPEN_AI_ORG_ID, a missing one fails the first call
+    pub fn new(config: &AppConfig) -> Result<Self, reqwest::Error> {
+        dotenv().ok();
+        // No overall timeout: a long completion streams for minutes
+        let mut client_builder = Client::builder().connect_timeout(LLM_CONNECT_TIMEOUT);
+        if let Some(proxy) = &config.http_proxy {
+            client_builder = client_builder.proxy(Proxy::all(proxy)?);
+        }
+        let cache = config.is_cache_enabled.then(|| {
+            ResponseCache::new(&config.cache_dir.join(RESPONSE_CACHE_DIR), config.cache_ttl)
+        });
+
+        Ok(Self {
+            client: client_builder.build()?,
+            api_key: env::var("OPEN_AI_KEY").ok(),
+            api_org: env::var("OPEN_AI_ORG_ID").ok(),
+            cache,
+            is_replay_only: config.is_replay_only,
+        })
+    }
+
+    // None with --no-cache
+    pub fn cache(&self) -> Option<&ResponseCache> {
+        self.cache.as_ref()
+    }
+
+    //Call Large Language Model (i.e. GPT-4)
+    pub async fn call_gpt(
+        &self,
+        messages: Vec<Message>,
+        settings: &ModelSettings,
+        structured_output: Option<&StructuredOutput>,
+    ) -> Result<String, LlmError> {
+        self.call_gpt_stream(messages, settings, structured_output, &mut |_| {})
+            .await
+    }
+
+    // Same as call_gpt, but every piece of the response is passed to on_chunk as soon as it arrives
+    // With a structured output, the response is the JSON arguments of the function call
+    #[instrument(
+        name = "llm.request",
+        skip_all,
+        fields(
+            provider = settings.provider.name(),
+            model = %settings.model,
+            is_cached = field::Empty
+        )
+    )]
+    pub async fn call_gpt_stream(
+        &self,
+        messages: Vec<Message>,
+        settings: &ModelSettings,
+        structured_output: Option<&StructuredOutput>,
+        on_chunk: &mut (dyn FnMut(&str) + Send),
+    ) -> Result<String, LlmError> {
+        // Identical requests are answered from the on-disk cache
+        let cache_key = ResponseCache::key(
+            settings.provider.name(),
+            &settings.model,
+            settings.temperature,
+            settings.max_tokens,
+            &messages,
+            structured_output,
+        );
+        // With --recorded the cache is all there is, even for a retry
+        let is_cache_read = !settings.is_cache_bypassed || self.is_replay_only;
+        let cached_response = self
+            .cache
+            .as_ref()
+            .filter(|_| is_cache_read)
+            .and_then(|cache| cache.get(&cache_key));
+        if let Some(cached_response) = cached_response {
+            Span::current().record("is_cached", true);
+            on_chunk(&cached_response);
+            return Ok(cached_response);
+        }
+        if self.is_replay_only {
+            return Err(LlmError::NotRecorded(cache_key));
+        }
+
+        let api_key = self.api_key.as_ref().ok_or_else(|| {
+            LlmError::Auth("OPEN_AI_KEY not found in environment variables".to_string())
+        })?;
+        let api_org = self.api_org.as_ref().ok_or_else(|| {
+            LlmError::Auth("OPEN_AI_ORG_ID not found in environment variables".to_string())
+        })?;
+
+        let chat_completion = ChatCompletion {
+            model: settings.model.clone(),
+            messages,
+            temperature: settings.temperature,
+            max_tokens: settings.max_tokens,
+            tools: structured_output.map(|structured_output| {
+                vec![Tool {
+                    tool_type: "function".to_string(),
+                    function: FunctionDefinition {
+                        name: structured_output.name.clone(),
+                        parameters: structured_output.schema.clone(),
+                    },
+                }]
+            }),
+            tool_choice: structured_output.map(|structured_output| ToolChoice {
+                tool_type: "function".to_string(),
+                function: ToolChoiceFunction {
+                    name: structured_output.name.clone(),
+                },
+            }),
+            stream: true,
+        };
+
+        let response = self
+            .client
+            .post(settings.provider.chat_completions_url())
+            .bearer_auth(api_key)
+            .header("OpenAI-Organization", api_org)
+            .json(&chat_completion)
+            .send()
+            .await
+            .map_err(|e| LlmError::Network(e.to_string()))?;
+
+        // Error bodies are plain JSON, not events
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = parse_retry_after(response.headers());
+            let body = response.text().await.unwrap_or_default();
+            return Err(LlmError::from_status(status.as_u16(), retry_after, &body));
+        }
+
+        // Server-sent events: the message is the concatenation of the delta of every chunk
+        let mut llm_response = String::new();
+        let mut sse_buffer = SseBuffer::default();
+        let mut byte_stream = response.bytes_stream();
+        'stream: while let Some(bytes) = byte_stream.next().await {
+            let bytes = bytes.map_err(|e| LlmError::Network(e.to_string()))?;
+            for data in sse_buffer.push(&bytes) {
+                if data == "[DONE]" {
+                    break 'stream;
+                }
+                let chunk: APIStreamChunk = serde_json::from_str(&data)
+                    .map_err(|e| LlmError::Network(format!("malformed event {}: {}", data, e)))?;
+                for choice in chunk.choices {
+                    let arguments = choice
+                        .delta
+                        .tool_calls
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|tool_call| tool_call.function?.arguments);
+                    for content in choice.delta.content.into_iter().chain(arguments) {
+                        on_chunk(&content);
+                        llm_response.push_str(&content);
+                    }
+                }
+            }
+        }
+        if llm_response.is_empty() {
+            return Err(LlmError::EmptyResponse(api_error_message(
+                &sse_buffer.rest(),
+            )));
+        }
+
+        if let Some(cache) = &self.cache {
+            // A cache which cannot be written only costs a new request next time
+            cache.put(&cache_key, &llm_response).ok();
+        }
+        Ok(llm_response)
+    }
+}
+
+// Splits a byte stream into the data payloads of its server-sent events
+#[derive(Debug, Default)]
+pub struct SseBuffer {
+    pending: Vec<u8>,
+}
+
+impl SseBuffer {
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut payloads: Vec<String> = Vec::new();
+        while let Some(newline) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=newline).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(data) = line.trim().strip_prefix("data:") {
+                payloads.push(data.trim().to_string());
+            }
+        }
+        payloads
+    }
+
+    // Whatever was not an event, e.g. the JSON body of an API error
+    pub fn rest(&self) -> String {
+        String::from_utf8_lossy(&self.pending).trim().to_string()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::general::app_context::AppContext;
 
     #[tokio::test]
     async fn test_call_gpt() {
@@ -72,23 +450,169 @@ mod tests {
             content: "Hi there, this is a test. Give me a REAL short response".to_string(),
         };
 
-        let mut messages = vec![];
-        messages.push(message);
+        let messages = vec![message];
 
         // Act
-        let response = call_gpt(messages).await;
+        let context = AppContext::new(AppConfig::load(&[]).expect("Invalid configuration"))
+            .expect("Failed to create context");
+        let settings = context.config.model_for("Tester", "test_call_gpt");
+        let response = context.llm.call_gpt(messages, &settings, None).await;
         match response {
             Ok(res_str) => {
                 dbg!(res_str);
-                assert!(true);
             }
             Err(e) => {
-                dbg!(e);
-                assert!(false);
+                panic!("call_gpt failed: {}", e);
             }
         }
 
         // Assert
         // TODO: Add assertions based on the expected behavior of call_gpt
     }
+
+    #[test]
+    fn test_sse_buffer_splits_events_across_chunks() {
+        let mut sse_buffer = SseBuffer::default();
+        let first = sse_buffer
+            .push(b"data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"cho");
+        assert_eq!(first.len(), 1);
+        let chunk: APIStreamChunk = serde_json::from_str(&first[0]).unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("Hel"));
+
+        let second =
+            sse_buffer.push(b"ices\":[{\"delta\":{\"content\":\"lo\"}}]}\n\ndata: [DONE]\n\n");
+        assert_eq!(second.len(), 2);
+        let chunk: APIStreamChunk = serde_json::from_str(&second[0]).unwrap();
+        assert_eq!(chunk.choices[0].delta.content.as_deref(), Some("lo"));
+        assert_eq!(second[1], "[DONE]");
+        assert_eq!(sse_buffer.rest(), "");
+    }
+
+    #[test]
+    fn test_llm_errors_are_classified_by_status() {
+        let body = r#"{"error": {"message": "Rate limit reached for gpt-4", "type": "requests"}}"#;
+        let error = LlmError::from_status(429, Some(Duration::from_secs(3)), body);
+        assert!(error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "rate limited: Rate limit reached for gpt-4"
+        );
+
+        assert!(LlmError::from_status(503, None, "").is_retryable());
+        assert!(!LlmError::from_status(401, None, "").is_retryable());
+        let invalid = LlmError::from_status(400, None, "context length exceeded");
+        assert!(!invalid.is_retryable());
+        assert_eq!(
+            invalid.to_string(),
+            "invalid request 400: context length exceeded"
+        );
+    }
+
+    #[test]
+    fn test_retry_policy_backs_off_and_honors_retry_after() {
+        let policy = RetryPolicy::default();
+        let server_error = LlmError::Server {
+            status: 500,
+            message: String::new(),
+        };
+        assert_eq!(
+            policy.delay(1, &server_error, 0.0),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            policy.delay(3, &server_error, 0.0),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(
+            policy.delay(3, &server_error, 1.0),
+            Some(Duration::from_secs(6))
+        );
+        assert_eq!(policy.delay(policy.max_attempts, &server_error, 0.0), None);
+
+        let rate_limited = LlmError::RateLimited {
+            retry_after: Some(Duration::from_secs(20)),
+            message: String::new(),
+        };
+        assert_eq!(
+            policy.delay(1, &rate_limited, 0.5),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(policy.delay(1, &LlmError::Auth(String::new()), 0.0), None);
+    }
+
+    #[tokio::test]
+    async fn test_replay_only_never_calls_the_api() {
+        let config = AppConfig {
+            cache_dir: std::env::temp_dir()
+                .join("auto_gippity_tests")
+                .join(format!("replay_{}", std::process::id())),
+            is_replay_only: true,
+            ..AppConfig::default()
+        };
+        let llm = LlmClient::new(&config).unwrap();
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "never recorded".to_string(),
+        }];
+        let settings = config.model_for("Tester", "test_replay_only");
+
+        let error = llm
+            .call_gpt(messages.clone(), &settings, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, LlmError::NotRecorded(_)));
+        assert!(!error.is_retryable());
+
+        let cache = llm.cache().unwrap();
+        let key = ResponseCache::key(
+            settings.provider.name(),
+            &settings.model,
+            settings.temperature,
+            settings.max_tokens,
+            &messages,
+            None,
+        );
+        cache.put(&key, "recorded answer").unwrap();
+        assert_eq!(
+            llm.call_gpt(messages, &settings, None).await.unwrap(),
+            "recorded answer"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retried_calls_bypass_the_cache() {
+        let config = AppConfig {
+            cache_dir: std::env::temp_dir()
+                .join("auto_gippity_tests")
+                .join(format!("bypass_{}", std::process::id())),
+            ..AppConfig::default()
+        };
+        let mut llm = LlmClient::new(&config).unwrap();
+        // Any request reaching the API fails before it is sent
+        llm.api_key = None;
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "print the code".to_string(),
+        }];
+        let mut settings = config.model_for("Tester", "test_bypass");
+        let key = ResponseCache::key(
+            settings.provider.name(),
+            &settings.model,
+            settings.temperature,
+            settings.max_tokens,
+            &messages,
+            None,
+        );
+        llm.cache().unwrap().put(&key, "broken code").unwrap();
+        assert_eq!(
+            llm.call_gpt(messages.clone(), &settings, None)
+                .await
+                .unwrap(),
+            "broken code"
+        );
+
+        settings.is_cache_bypassed = true;
+        let error = llm.call_gpt(messages, &settings, None).await.unwrap_err();
+        assert!(matches!(error, LlmError::Auth(_)));
+    }
 }
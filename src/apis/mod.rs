@@ -1 +1,3 @@
 pub mod call_request;
+pub mod recorded_responses;
+pub mod response_cache;
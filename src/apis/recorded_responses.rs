@@ -0,0 +1,63 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::helpers::events::{EventRecord, RunEvent};
+
+// LLM responses and user answers of an earlier run, replayed instead of calling the API
+// or asking the user. The n-th call of a function by an agent gets the n-th response
+// that agent got, so the manager's scope preview never reaches the architect.
+// Code approvals are not replayed, the user is asked again.
+#[derive(Debug, Default)]
+pub struct RecordedResponses {
+    // (agent, function) -> responses in call order
+    responses: Mutex<HashMap<(String, String), VecDeque<String>>>,
+    // prompt -> answers in the order they were given
+    inputs: Mutex<HashMap<String, VecDeque<serde_json::Value>>>,
+}
+
+impl RecordedResponses {
+    pub fn from_events(events: &[EventRecord]) -> Self {
+        let mut responses: HashMap<(String, String), VecDeque<String>> = HashMap::new();
+        let mut inputs: HashMap<String, VecDeque<serde_json::Value>> = HashMap::new();
+        for record in events {
+            match &record.event {
+                RunEvent::LlmCall {
+                    agent,
+                    function,
+                    response,
+                    ..
+                } => responses
+                    .entry((agent.clone(), function.clone()))
+                    .or_default()
+                    .push_back(response.clone()),
+                RunEvent::UserInput { prompt, answer } => inputs
+                    .entry(prompt.clone())
+                    .or_default()
+                    .push_back(answer.clone()),
+                _ => {}
+            }
+        }
+        Self {
+            responses: Mutex::new(responses),
+            inputs: Mutex::new(inputs),
+        }
+    }
+
+    // None once every recorded response of this agent and function was used
+    pub fn next(&self, agent: &str, function: &str) -> Option<String> {
+        self.responses
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(&(agent.to_string(), function.to_string()))
+            .and_then(VecDeque::pop_front)
+    }
+
+    // None once every recorded answer to this prompt was used
+    pub fn next_input(&self, prompt: &str) -> Option<serde_json::Value> {
+        self.inputs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(prompt)
+            .and_then(VecDeque::pop_front)
+    }
+}
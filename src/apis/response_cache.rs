@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::apis::call_request::StructuredOutput;
+use crate::models::general::llm::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    created_at: u64,
+    response: String,
+}
+
+// Content addressed store of LLM responses, one file per request
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    // Shared by the clones, i.e. by every agent of the run
+    hits: Arc<AtomicUsize>,
+    misses: Arc<AtomicUsize>,
+}
+
+impl ResponseCache {
+    pub fn new(dir: &Path, ttl: Duration) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            ttl,
+            hits: Arc::new(AtomicUsize::new(0)),
+            misses: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    // Hash of everything which changes the response
+    pub fn key(
+        provider: &str,
+        model: &str,
+        temperature: f32,
+        max_tokens: Option<u32>,
+        messages: &[Message],
+        structured_output: Option<&StructuredOutput>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(provider.as_bytes());
+        hasher.update([0]);
+        hasher.update(model.as_bytes());
+        hasher.update([0]);
+        hasher.update(temperature.to_bits().to_le_bytes());
+        // A response cut short by a low limit must not answer a call allowing more
+        hasher.update(max_tokens.unwrap_or_default().to_le_bytes());
+        hasher.update(serde_json::to_vec(messages).expect("Failed to encode messages"));
+        // Left out when absent, so text responses keep the keys they were recorded with
+        if let Some(structured_output) = structured_output {
+            hasher.update(
+                serde_json::to_vec(structured_output).expect("Failed to encode structured output"),
+            );
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    // Counts a hit or a miss, expired entries are misses
+    pub fn get(&self, key: &str) -> Option<String> {
+        let cached: Option<CachedResponse> = std::fs::read_to_string(self.entry_path(key))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+
+        match cached {
+            Some(cached) if unix_now().saturating_sub(cached.created_at) < self.ttl.as_secs() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(cached.response)
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn put(&self, key: &str, response: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let cached = CachedResponse {
+            created_at: unix_now(),
+            response: response.to_string(),
+        };
+        std::fs::write(
+            self.entry_path(key),
+            serde_json::to_string(&cached).expect("Failed to encode cached response"),
+        )
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(content: &str) -> Vec<Message> {
+        vec![Message {
+            role: "system".to_string(),
+            content: content.to_string(),
+        }]
+    }
+
+    fn test_cache(name: &str, ttl: Duration) -> ResponseCache {
+        let dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        ResponseCache::new(&dir, ttl)
+    }
+
+    #[test]
+    fn test_key_covers_model_temperature_limit_and_messages() {
+        let key = ResponseCache::key("openai", "gpt-4", 0.1, None, &messages("hello"), None);
+        assert_eq!(
+            key,
+            ResponseCache::key("openai", "gpt-4", 0.1, None, &messages("hello"), None)
+        );
+        assert_eq!(key.len(), 64);
+        assert_ne!(
+            key,
+            ResponseCache::key(
+                "openai",
+                "gpt-3.5-turbo",
+                0.1,
+                None,
+                &messages("hello"),
+                None
+            )
+        );
+        assert_ne!(
+            key,
+            ResponseCache::key("openai", "gpt-4", 0.7, None, &messages("hello"), None)
+        );
+        assert_ne!(
+            key,
+            ResponseCache::key("openai", "gpt-4", 0.1, None, &messages("hello!"), None)
+        );
+        assert_ne!(
+            key,
+            ResponseCache::key("openai", "gpt-4", 0.1, Some(256), &messages("hello"), None)
+        );
+        let structured_output = StructuredOutput {
+            name: "print_project_scope".to_string(),
+            schema: serde_json::json!({ "type": "object" }),
+        };
+        assert_ne!(
+            key,
+            ResponseCache::key(
+                "openai",
+                "gpt-4",
+                0.1,
+                None,
+                &messages("hello"),
+                Some(&structured_output)
+            )
+        );
+    }
+
+    #[test]
+    fn test_cache_hit_miss_and_ttl() {
+        let cache = test_cache("cache_ttl", Duration::from_secs(60));
+        let key = ResponseCache::key("openai", "gpt-4", 0.1, None, &messages("goal"), None);
+        assert_eq!(cache.get(&key), None);
+        cache.put(&key, "build a website that ...").unwrap();
+        assert_eq!(
+            cache.get(&key),
+            Some("build a website that ...".to_string())
+        );
+
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 1 });
+
+        let expired_cache = ResponseCache::new(&cache.dir, Duration::from_secs(0));
+        assert_eq!(expired_cache.get(&key), None);
+        std::fs::remove_dir_all(&cache.dir).ok();
+    }
+}
@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::agents::agent_traits::{FactSheet, RouteObject};
+
+// What a correct run of a corpus project produces, unset properties are not checked
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedProperties {
+    pub is_crud_required: Option<bool>,
+    pub is_user_login_and_logout: Option<bool>,
+    pub is_external_urls_required: Option<bool>,
+    // "METHOD /route", dynamic segments match whatever their name, e.g. "GET /todos/{id}"
+    #[serde(default)]
+    pub required_routes: Vec<String>,
+    #[serde(default)]
+    pub must_compile: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub is_passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkScore {
+    pub checks: Vec<CheckResult>,
+}
+
+impl BenchmarkScore {
+    // Share of passed checks, between 0 and 1, None without any check
+    pub fn value(&self) -> Option<f64> {
+        if self.checks.is_empty() {
+            return None;
+        }
+        let passed = self.checks.iter().filter(|check| check.is_passed).count();
+        Some(passed as f64 / self.checks.len() as f64)
+    }
+
+    pub fn failed_checks(&self) -> Vec<&str> {
+        self.checks
+            .iter()
+            .filter(|check| !check.is_passed)
+            .map(|check| check.name.as_str())
+            .collect()
+    }
+}
+
+// "get /todos/{todo_id}" and "GET /todos/{id}" are the same route
+fn normalize_route(method: &str, route: &str) -> String {
+    let path: Vec<String> = route
+        .trim()
+        .trim_end_matches('/')
+        .split('/')
+        .map(|segment| {
+            if segment.starts_with('{') && segment.ends_with('}') {
+                "{}".to_string()
+            } else {
+                segment.to_lowercase()
+            }
+        })
+        .collect();
+    format!("{} {}", method.trim().to_uppercase(), path.join("/"))
+}
+
+// Compares the FactSheet and the routes of the generated project with the expectations
+pub fn score_project(
+    expected: &ExpectedProperties,
+    factsheet: Option<&FactSheet>,
+    generated_routes: &[RouteObject],
+    is_compiled: bool,
+) -> BenchmarkScore {
+    let mut checks = Vec::new();
+    let scope = factsheet.and_then(|factsheet| factsheet.project_scope.as_ref());
+    let scope_flags = [
+        (
+            "is_crud_required",
+            expected.is_crud_required,
+            scope.map(|scope| scope.is_crud_required),
+        ),
+        (
+            "is_user_login_and_logout",
+            expected.is_user_login_and_logout,
+            scope.map(|scope| scope.is_user_login_and_logout),
+        ),
+        (
+            "is_external_urls_required",
+            expected.is_external_urls_required,
+            scope.map(|scope| scope.is_external_urls_required),
+        ),
+    ];
+    for (name, expected_flag, actual_flag) in scope_flags {
+        if let Some(expected_flag) = expected_flag {
+            checks.push(CheckResult {
+                name: format!("{} = {}", name, expected_flag),
+                is_passed: actual_flag == Some(expected_flag),
+            });
+        }
+    }
+
+    let routes: Vec<String> = generated_routes
+        .iter()
+        .map(|route| normalize_route(&route.method, &route.route))
+        .collect();
+    for required_route in &expected.required_routes {
+        let (method, route) = required_route
+            .split_once(' ')
+            .unwrap_or(("GET", required_route.as_str()));
+        checks.push(CheckResult {
+            name: format!("route {}", required_route),
+            is_passed: routes.contains(&normalize_route(method, route)),
+        });
+    }
+
+    if expected.must_compile {
+        checks.push(CheckResult {
+            name: "compiles".to_string(),
+            is_passed: is_compiled,
+        });
+    }
+    BenchmarkScore { checks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::ProjectScope;
+
+    fn route(method: &str, path: &str) -> RouteObject {
+        RouteObject {
+            route: path.to_string(),
+            is_route_dynamic: path.contains('{').to_string(),
+            is_route_protected: "false".to_string(),
+            method: method.to_string(),
+            request_body: serde_json::Value::Null,
+            response: serde_json::Value::Null,
+        }
+    }
+
+    #[test]
+    fn test_score_project_checks_scope_routes_and_build() {
+        let expected = ExpectedProperties {
+            is_crud_required: Some(true),
+            is_user_login_and_logout: Some(false),
+            required_routes: vec!["GET /todos/{id}".to_string(), "POST /todos".to_string()],
+            must_compile: true,
+            ..ExpectedProperties::default()
+        };
+        let mut factsheet: FactSheet =
+            serde_json::from_str(r#"{ "project_description": "build a todo app" }"#).unwrap();
+        factsheet.project_scope = Some(ProjectScope {
+            is_crud_required: true,
+            is_user_login_and_logout: true,
+            is_external_urls_required: false,
+        });
+        let routes = vec![route("get", "/todos/{todo_id}/"), route("get", "/todos")];
+
+        let score = score_project(&expected, Some(&factsheet), &routes, true);
+        assert_eq!(score.value(), Some(0.6));
+        assert_eq!(
+            score.failed_checks(),
+            vec!["is_user_login_and_logout = false", "route POST /todos"]
+        );
+
+        let failed_run = score_project(&expected, None, &[], false);
+        assert_eq!(failed_run.value(), Some(0.0));
+        assert_eq!(
+            score_project(&ExpectedProperties::default(), None, &[], false).value(),
+            None
+        );
+    }
+}
@@ -1,11 +1,20 @@
 use crossterm::{
-    style::{Color, Print, ResetColor, SetForegroundColor},
+    style::{Color, ResetColor, SetForegroundColor},
     ExecutableCommand,
 };
 
-use std::io::{stdin, stdout};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{stdin, stdout, Write};
+use std::path::Path;
 
-#[derive(Debug, PartialEq)]
+use crate::helpers::dashboard;
+use crate::helpers::eval::EvalReport;
+use crate::helpers::events::{self, EventRecord, EventSubscriber, RunEvent};
+use crate::models::agents::agent_traits::ProjectScope;
+use crate::models::general::app_context::AppContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum PrintCommand {
     AICall,
     UnitTest,
@@ -13,7 +22,16 @@ pub enum PrintCommand {
 }
 
 impl PrintCommand {
+    // Sent to the event stream, the terminal shows it through TerminalPrinter or the dashboard
     pub fn print_agent_message(&self, agent_pos: &str, agent_statement: &str) {
+        events::emit(RunEvent::AgentMessage {
+            agent: agent_pos.to_string(),
+            kind: *self,
+            message: agent_statement.to_string(),
+        });
+    }
+
+    fn print_line(&self, agent_pos: &str, agent_statement: &str) {
         let mut stdout: std::io::Stdout = stdout();
 
         let statement_color = match self {
@@ -30,13 +48,51 @@ impl PrintCommand {
     }
 }
 
+// Colored agent messages on stdout, the view without the dashboard
+#[derive(Debug)]
+pub struct TerminalPrinter;
+
+impl EventSubscriber for TerminalPrinter {
+    fn on_event(&self, record: &EventRecord) {
+        if let RunEvent::AgentMessage {
+            agent,
+            kind,
+            message,
+        } = &record.event
+        {
+            kind.print_line(agent, message);
+        }
+    }
+}
+
+// The answer is recorded for replays, which read it back instead of asking again.
+// A replay past the recorded answers asks the user
+pub fn user_input<T: Serialize + DeserializeOwned>(
+    context: &AppContext,
+    prompt: &str,
+    ask: impl FnOnce() -> T,
+) -> T {
+    let recorded_answer = context
+        .replay
+        .as_ref()
+        .and_then(|replay| replay.next_input(prompt))
+        .and_then(|answer| serde_json::from_value(answer).ok());
+    let answer = recorded_answer.unwrap_or_else(ask);
+    events::emit(RunEvent::UserInput {
+        prompt: prompt.to_string(),
+        answer: serde_json::to_value(&answer).expect("User answers are always valid JSON"),
+    });
+    answer
+}
+
 //Get user request
 pub fn get_use_response(question: &str) -> String {
+    let _terminal = dashboard::release_terminal();
     let mut stdout: std::io::Stdout = std::io::stdout();
 
     //Print the question in a specific color
     stdout.execute(SetForegroundColor(Color::Cyan)).unwrap();
-    println!("");
+    println!();
     println!("{}", question);
 
     //Reset the color
@@ -47,16 +103,17 @@ pub fn get_use_response(question: &str) -> String {
     stdin()
         .read_line(&mut user_response)
         .expect("Failed to read response");
-    return user_response.trim().to_string();
+    user_response.trim().to_string()
 }
 
 // Get user review the AI-generated codes
 pub fn confirm_safe_code() -> bool {
+    let _terminal = dashboard::release_terminal();
     let mut stdout: std::io::Stdout = std::io::stdout();
 
     loop {
         stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
-        println!("");
+        println!();
         println!("WARNING: you are about to execute code written ENTIRELY by AI.\n Please review the code carefully before executing it.");
 
         stdout.execute(ResetColor).unwrap();
@@ -78,6 +135,251 @@ pub fn confirm_safe_code() -> bool {
     }
 }
 
+// What the user does with the goal and scope found by the manager
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BriefReview {
+    Confirm,
+    EditGoal,
+    EditScope,
+}
+
+// Get user confirm the project before any code is written
+pub fn review_project_brief(goal: &str, scope: &ProjectScope) -> BriefReview {
+    let _terminal = dashboard::release_terminal();
+    let mut stdout: std::io::Stdout = std::io::stdout();
+
+    loop {
+        stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
+        println!();
+        println!("Goal: {}", goal);
+        println!(
+            "Scope: crud={} login={} urls={}",
+            scope.is_crud_required, scope.is_user_login_and_logout, scope.is_external_urls_required
+        );
+
+        stdout.execute(ResetColor).unwrap();
+        stdout.execute(SetForegroundColor(Color::Green)).unwrap();
+        println!("[1] Looks good, build it.");
+        stdout.execute(SetForegroundColor(Color::Yellow)).unwrap();
+        println!("[2] Edit the goal");
+        println!("[3] Edit the scope");
+        stdout.execute(ResetColor).unwrap();
+
+        //Read user input
+        let mut user_response = String::new();
+        stdin()
+            .read_line(&mut user_response)
+            .expect("Failed to read response");
+        match user_response.trim() {
+            "1" => return BriefReview::Confirm,
+            "2" => return BriefReview::EditGoal,
+            "3" => return BriefReview::EditScope,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointChoice {
+    Accept,
+    Edit,
+    Skip,
+}
+
+// Get user check the FactSheet before the next agent uses it
+pub fn review_checkpoint(finished: &str, path: &Path) -> CheckpointChoice {
+    let _terminal = dashboard::release_terminal();
+    let mut stdout: std::io::Stdout = std::io::stdout();
+
+    loop {
+        stdout.execute(SetForegroundColor(Color::Blue)).unwrap();
+        println!();
+        println!(
+            "{} finished, the FactSheet is in {}",
+            finished,
+            path.display()
+        );
+
+        stdout.execute(ResetColor).unwrap();
+        stdout.execute(SetForegroundColor(Color::Green)).unwrap();
+        println!("[1] Continue with this FactSheet");
+        stdout.execute(SetForegroundColor(Color::Yellow)).unwrap();
+        println!("[2] Edit it in $EDITOR");
+        println!("[3] Continue and stop asking");
+        stdout.execute(ResetColor).unwrap();
+
+        //Read user input
+        let mut user_response = String::new();
+        stdin()
+            .read_line(&mut user_response)
+            .expect("Failed to read response");
+        match user_response.trim() {
+            "1" => return CheckpointChoice::Accept,
+            "2" => return CheckpointChoice::Edit,
+            "3" => return CheckpointChoice::Skip,
+            _ => {}
+        }
+    }
+}
+
+// How the response of the LLM is shown while it is streamed
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StreamOutput {
+    // Every token as it arrives
+    Live,
+    // A single line counting the received characters
+    #[default]
+    Progress,
+    Off,
+}
+
+impl StreamOutput {
+    // "live", "progress" (default) or "off"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "live" => Some(StreamOutput::Live),
+            "progress" => Some(StreamOutput::Progress),
+            "off" => Some(StreamOutput::Off),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StreamPrinter {
+    output: StreamOutput,
+    received_chars: usize,
+}
+
+impl StreamPrinter {
+    pub fn new(output: StreamOutput) -> Self {
+        Self {
+            output,
+            received_chars: 0,
+        }
+    }
+
+    pub fn print_chunk(&mut self, chunk: &str) {
+        self.received_chars += chunk.chars().count();
+        if self.output != StreamOutput::Off && dashboard::stream_progress(self.received_chars) {
+            return;
+        }
+        let mut stdout: std::io::Stdout = stdout();
+
+        match self.output {
+            StreamOutput::Live => {
+                stdout.execute(SetForegroundColor(Color::DarkGrey)).unwrap();
+                print!("{}", chunk);
+                stdout.execute(ResetColor).unwrap();
+            }
+            StreamOutput::Progress => {
+                stdout.execute(SetForegroundColor(Color::DarkGrey)).unwrap();
+                print!("\r  ... {} characters received", self.received_chars);
+                stdout.execute(ResetColor).unwrap();
+            }
+            StreamOutput::Off => return,
+        }
+        stdout.flush().ok();
+    }
+
+    // Ends the streamed line, if anything was printed
+    pub fn finish(&mut self) {
+        if dashboard::stream_progress(0) {
+            self.received_chars = 0;
+            return;
+        }
+        if self.output != StreamOutput::Off && self.received_chars > 0 {
+            println!();
+        }
+        self.received_chars = 0;
+    }
+}
+
+// Printed once the project is done, whether it succeeded or not
+pub fn print_run_summary(context: &AppContext, run_dir: Option<&Path>) {
+    let mut stdout: std::io::Stdout = std::io::stdout();
+
+    stdout.execute(SetForegroundColor(Color::Cyan)).unwrap();
+    println!();
+    println!("Run summary:");
+    stdout.execute(ResetColor).unwrap();
+
+    if let Some(cache) = context.llm.cache() {
+        let stats = cache.stats();
+        println!(
+            "  LLM response cache: {} hits, {} misses",
+            stats.hits, stats.misses
+        );
+    } else {
+        println!("  LLM response cache: disabled (--no-cache)");
+    }
+
+    let metrics = context.metrics.snapshot();
+    println!(
+        "  LLM calls: {}, about {} tokens",
+        metrics.llm_calls, metrics.estimated_tokens
+    );
+    println!(
+        "  Builds: {} ok out of {}, {} fix rounds, endpoints: {} passed out of {}",
+        metrics.successful_builds,
+        metrics.builds,
+        metrics.fix_rounds,
+        metrics.endpoint_passes,
+        metrics.endpoint_checks
+    );
+    if let Some(run_dir) = run_dir {
+        println!("  Event log and FactSheet: {}", run_dir.display());
+    }
+}
+
+fn percent(value: Option<f64>) -> String {
+    value.map_or("-".to_string(), |value| format!("{:.0}%", value * 100.0))
+}
+
+// Benchmark score of every project, then one line per prompt version, the baseline first
+pub fn print_eval_summary(eval_report: &EvalReport) {
+    let mut stdout: std::io::Stdout = std::io::stdout();
+
+    stdout.execute(SetForegroundColor(Color::Cyan)).unwrap();
+    println!();
+    println!(
+        "{:<20} {:<16} {:>6}  Failed checks",
+        "Project", "Prompt version", "Score"
+    );
+    stdout.execute(ResetColor).unwrap();
+    for outcome in &eval_report.projects {
+        println!(
+            "{:<20} {:<16} {:>6}  {}",
+            outcome.project,
+            outcome.version,
+            percent(outcome.score),
+            outcome.failed_checks.join(", ")
+        );
+    }
+
+    stdout.execute(SetForegroundColor(Color::Cyan)).unwrap();
+    println!();
+    println!(
+        "{:<16} {:>8} {:>10} {:>10} {:>10} {:>12} {:>6}",
+        "Prompt version", "Projects", "Compiled", "Fix rounds", "Endpoints", "Tokens", "Score"
+    );
+    stdout.execute(ResetColor).unwrap();
+    for summary in &eval_report.summaries {
+        println!(
+            "{:<16} {:>8} {:>10} {:>10.1} {:>10} {:>12.0} {:>6}",
+            summary.version,
+            summary.projects,
+            percent(Some(summary.compile_success_rate)),
+            summary.mean_fix_rounds,
+            percent(Some(summary.endpoint_pass_rate)),
+            summary.mean_estimated_tokens,
+            percent(summary.mean_score)
+        );
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +391,68 @@ mod tests {
 
     #[test]
     fn test_get_use_response() {}
+
+    #[test]
+    fn test_replay_reads_back_answers_and_agent_responses() {
+        use crate::apis::recorded_responses::RecordedResponses;
+        use crate::models::general::config::AppConfig;
+
+        let record = |event: RunEvent| EventRecord {
+            timestamp_ms: 0,
+            run_id: None,
+            event,
+        };
+        let llm_call = |agent: &str, response: &str| {
+            record(RunEvent::LlmCall {
+                agent: agent.to_string(),
+                function: "print_project_scope".to_string(),
+                prompt: Vec::new(),
+                response: response.to_string(),
+                estimated_tokens: 0,
+                latency_ms: 0,
+                attempts: 1,
+            })
+        };
+        let events = vec![
+            llm_call("Project Manager", "manager preview"),
+            record(RunEvent::UserInput {
+                prompt: "Project brief".to_string(),
+                answer: serde_json::json!("edit_goal"),
+            }),
+            record(RunEvent::UserInput {
+                prompt: "New goal:".to_string(),
+                answer: serde_json::json!("a todo api with tags"),
+            }),
+            llm_call("Solutions Architect", "architect scope"),
+        ];
+        let mut context = AppContext::new(AppConfig::default()).unwrap();
+        context.replay = Some(RecordedResponses::from_events(&events));
+        let replay = context.replay.as_ref().unwrap();
+
+        assert_eq!(
+            replay
+                .next("Solutions Architect", "print_project_scope")
+                .as_deref(),
+            Some("architect scope")
+        );
+        assert_eq!(
+            replay
+                .next("Project Manager", "print_project_scope")
+                .as_deref(),
+            Some("manager preview")
+        );
+
+        let not_asked = || -> BriefReview { panic!("the recorded answer is used") };
+        assert_eq!(
+            user_input(&context, "Project brief", not_asked),
+            BriefReview::EditGoal
+        );
+        let goal: String = user_input(&context, "New goal:", || unreachable!());
+        assert_eq!(goal, "a todo api with tags");
+        // Past the recording, the user is asked
+        assert_eq!(
+            user_input(&context, "Project brief", || BriefReview::Confirm),
+            BriefReview::Confirm
+        );
+    }
 }
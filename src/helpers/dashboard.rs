@@ -0,0 +1,382 @@
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand, QueueableCommand,
+};
+use std::collections::VecDeque;
+use std::io::{stdout, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::events::{self, EventRecord, EventSubscriber, RunEvent};
+use crate::models::general::app_context::AppContext;
+use crate::models::general::run_metrics::MetricsSnapshot;
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+// Older lines are dropped, the log pane shows the latest ones that fit
+const MAX_LOG_LINES: usize = 500;
+
+// Set while the full screen dashboard runs, it gets the events and StreamPrinter writes to it
+static DASHBOARD: Mutex<Option<Dashboard>> = Mutex::new(None);
+
+#[derive(Debug, Default)]
+struct AgentPanel {
+    position: String,
+    state: String,
+    last_message: String,
+}
+
+#[derive(Debug)]
+struct LogLine {
+    position: String,
+    message: String,
+    is_issue: bool,
+}
+
+#[derive(Debug)]
+struct EndpointResult {
+    check: String,
+    is_passed: bool,
+}
+
+#[derive(Debug, Default)]
+struct Dashboard {
+    // In the order the agents first showed up
+    agents: Vec<AgentPanel>,
+    log: VecDeque<LogLine>,
+    endpoints: Vec<EndpointResult>,
+    // Characters of the LLM response being streamed, 0 when none is
+    streamed_chars: usize,
+    // The terminal is handed back to the user while they answer a question
+    is_paused: bool,
+}
+
+impl Dashboard {
+    fn agent(&mut self, position: &str) -> &mut AgentPanel {
+        let index = match self
+            .agents
+            .iter()
+            .position(|agent| agent.position == position)
+        {
+            Some(index) => index,
+            None => {
+                self.agents.push(AgentPanel {
+                    position: position.to_string(),
+                    ..AgentPanel::default()
+                });
+                self.agents.len() - 1
+            }
+        };
+        &mut self.agents[index]
+    }
+}
+
+fn with_dashboard<T>(update: impl FnOnce(&mut Dashboard) -> T) -> Option<T> {
+    let mut dashboard = DASHBOARD.lock().unwrap_or_else(|e| e.into_inner());
+    dashboard.as_mut().map(update)
+}
+
+// Subscriber updating the dashboard, replaces TerminalPrinter while it runs
+#[derive(Debug)]
+struct DashboardView;
+
+impl EventSubscriber for DashboardView {
+    fn on_event(&self, record: &EventRecord) {
+        with_dashboard(|dashboard| match &record.event {
+            RunEvent::AgentMessage {
+                agent,
+                kind,
+                message,
+            } => {
+                dashboard.agent(agent).last_message = message.clone();
+                dashboard.log.push_back(LogLine {
+                    position: agent.clone(),
+                    message: message.clone(),
+                    is_issue: *kind == PrintCommand::Issue,
+                });
+                if dashboard.log.len() > MAX_LOG_LINES {
+                    dashboard.log.pop_front();
+                }
+            }
+            RunEvent::StateChanged { agent, state } => {
+                dashboard.agent(agent).state = format!("{:?}", state)
+            }
+            RunEvent::EndpointTest {
+                route,
+                has_credentials,
+                expected_status,
+                status,
+                ..
+            } => {
+                let credentials = if *has_credentials { "with" } else { "without" };
+                dashboard.endpoints.push(EndpointResult {
+                    check: format!("{} {} credentials", route, credentials),
+                    is_passed: *status == Some(*expected_status),
+                })
+            }
+            _ => {}
+        });
+    }
+}
+
+// False when the dashboard is not running
+pub fn stream_progress(received_chars: usize) -> bool {
+    with_dashboard(|dashboard| dashboard.streamed_chars = received_chars).is_some()
+}
+
+// Gives the terminal back to the user until dropped, e.g. while reading an answer on stdin
+pub struct TerminalRelease;
+
+impl Drop for TerminalRelease {
+    fn drop(&mut self) {
+        let mut stdout = stdout();
+        stdout.execute(EnterAlternateScreen).ok();
+        stdout.execute(Hide).ok();
+        with_dashboard(|dashboard| dashboard.is_paused = false);
+    }
+}
+
+// None without a dashboard, or when the terminal is already released
+pub fn release_terminal() -> Option<TerminalRelease> {
+    let is_released = with_dashboard(|dashboard| {
+        let was_paused = dashboard.is_paused;
+        dashboard.is_paused = true;
+        !was_paused
+    })?;
+    if !is_released {
+        return None;
+    }
+    let mut stdout = stdout();
+    stdout.execute(Show).ok();
+    stdout.execute(LeaveAlternateScreen).ok();
+    Some(TerminalRelease)
+}
+
+fn fit(text: &str, width: usize) -> String {
+    let line: String = text.chars().take(width).collect();
+    format!("{:<width$}", line, width = width)
+}
+
+// Header, one line per agent, endpoint results, then the latest log lines that fit
+fn render_lines(
+    dashboard: &Dashboard,
+    metrics: &MetricsSnapshot,
+    width: usize,
+    height: usize,
+) -> Vec<(Color, String)> {
+    let mut lines = vec![
+        (
+            Color::Cyan,
+            format!(
+                " auto_gippity | LLM calls {}, ~{} tokens | builds {}/{} | fix rounds {} | endpoints {}/{}",
+                metrics.llm_calls,
+                metrics.estimated_tokens,
+                metrics.successful_builds,
+                metrics.builds,
+                metrics.fix_rounds,
+                metrics.endpoint_passes,
+                metrics.endpoint_checks
+            ),
+        ),
+        (Color::DarkGrey, "─".repeat(width)),
+    ];
+    for agent in &dashboard.agents {
+        let color = match agent.state.as_str() {
+            "Finished" => Color::Green,
+            "" => Color::DarkGrey,
+            _ => Color::Yellow,
+        };
+        lines.push((
+            color,
+            format!(
+                " {:<22} {:<12} {}",
+                agent.position, agent.state, agent.last_message
+            ),
+        ));
+    }
+    if !dashboard.endpoints.is_empty() {
+        lines.push((Color::DarkGrey, "─".repeat(width)));
+        for endpoint in &dashboard.endpoints {
+            let (color, result) = if endpoint.is_passed {
+                (Color::Green, "ok  ")
+            } else {
+                (Color::Red, "FAIL")
+            };
+            lines.push((color, format!(" {} {}", result, endpoint.check)));
+        }
+    }
+    lines.push((Color::DarkGrey, "─".repeat(width)));
+
+    let mut footer = Vec::new();
+    if dashboard.streamed_chars > 0 {
+        footer.push((
+            Color::DarkGrey,
+            format!("  ... {} characters received", dashboard.streamed_chars),
+        ));
+    }
+    let log_height = height.saturating_sub(lines.len() + footer.len());
+    let skipped = dashboard.log.len().saturating_sub(log_height);
+    for log_line in dashboard.log.iter().skip(skipped) {
+        let color = if log_line.is_issue {
+            Color::Red
+        } else {
+            Color::White
+        };
+        lines.push((
+            color,
+            format!(" {}: {}", log_line.position, log_line.message),
+        ));
+    }
+    lines.extend(footer);
+
+    lines.truncate(height);
+    lines
+        .into_iter()
+        .map(|(color, line)| (color, fit(&line, width)))
+        .collect()
+}
+
+fn draw(context: &AppContext) {
+    let (width, height) = terminal::size().unwrap_or((80, 24));
+    let Some(lines) = with_dashboard(|dashboard| {
+        (!dashboard.is_paused).then(|| {
+            render_lines(
+                dashboard,
+                &context.metrics.snapshot(),
+                width as usize,
+                height as usize,
+            )
+        })
+    })
+    .flatten() else {
+        return;
+    };
+
+    let mut stdout = stdout();
+    stdout.queue(Clear(ClearType::All)).ok();
+    for (row, (color, line)) in lines.iter().enumerate() {
+        stdout
+            .queue(MoveTo(0, row as u16))
+            .and_then(|stdout| stdout.queue(SetForegroundColor(*color)))
+            .and_then(|stdout| stdout.queue(Print(line)))
+            .ok();
+    }
+    stdout.queue(ResetColor).ok();
+    stdout.flush().ok();
+}
+
+fn leave_screen() {
+    let mut stdout = stdout();
+    stdout.execute(ResetColor).ok();
+    stdout.execute(Show).ok();
+    stdout.execute(LeaveAlternateScreen).ok();
+}
+
+#[derive(Debug)]
+pub struct DashboardHandle {
+    context: Arc<AppContext>,
+    view: Arc<dyn EventSubscriber>,
+    render_task: JoinHandle<()>,
+}
+
+// Full screen dashboard redrawn until stopped, the caller unsubscribes its TerminalPrinter meanwhile
+pub fn start(context: Arc<AppContext>) -> DashboardHandle {
+    *DASHBOARD.lock().unwrap_or_else(|e| e.into_inner()) = Some(Dashboard::default());
+    let view: Arc<dyn EventSubscriber> = Arc::new(DashboardView);
+    events::subscribe(view.clone());
+    let mut stdout = stdout();
+    stdout.execute(EnterAlternateScreen).ok();
+    stdout.execute(Hide).ok();
+
+    // A panic message must not be lost with the alternate screen
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |panic_info| {
+        leave_screen();
+        previous_hook(panic_info);
+    }));
+
+    let render_context = context.clone();
+    let render_task = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            draw(&render_context);
+        }
+    });
+    DashboardHandle {
+        context,
+        view,
+        render_task,
+    }
+}
+
+impl DashboardHandle {
+    // The agents and endpoint results stay on the normal screen, followed by plain output again
+    pub fn stop(self) {
+        self.render_task.abort();
+        events::unsubscribe(&self.view);
+        let lines = with_dashboard(|dashboard| {
+            let (width, _) = terminal::size().unwrap_or((80, 24));
+            render_lines(
+                dashboard,
+                &self.context.metrics.snapshot(),
+                width as usize,
+                // Everything but the log
+                3 + dashboard.agents.len()
+                    + dashboard.endpoints.len()
+                    + usize::from(!dashboard.endpoints.is_empty()),
+            )
+        });
+        *DASHBOARD.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        leave_screen();
+        for (_, line) in lines.unwrap_or_default() {
+            println!("{}", line.trim_end());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_lines_fit_the_terminal() {
+        let mut dashboard = Dashboard::default();
+        dashboard.agent("Solutions Architect").state = "Finished".to_string();
+        dashboard.agent("Backend Developer").state = "UnitTesting".to_string();
+        dashboard.endpoints.push(EndpointResult {
+            check: "/todos without credentials".to_string(),
+            is_passed: false,
+        });
+        for i in 0..20 {
+            dashboard.log.push_back(LogLine {
+                position: "Backend Developer".to_string(),
+                message: format!("message {}", i),
+                is_issue: i == 19,
+            });
+        }
+        let metrics = MetricsSnapshot {
+            llm_calls: 3,
+            fix_rounds: 1,
+            ..MetricsSnapshot::default()
+        };
+
+        let lines = render_lines(&dashboard, &metrics, 40, 10);
+        assert_eq!(lines.len(), 10);
+        assert!(lines.iter().all(|(_, line)| line.chars().count() == 40));
+        assert!(lines[0].1.starts_with(" auto_gippity | LLM calls 3"));
+        assert_eq!(lines[2].0, Color::Green);
+        assert!(lines[3].1.contains("UnitTesting"));
+        assert_eq!(
+            lines[5],
+            (Color::Red, fit(" FAIL /todos without credentials", 40))
+        );
+        // The latest log lines are kept
+        assert_eq!(lines[9].0, Color::Red);
+        assert!(lines[9].1.contains("message 19"));
+        assert!(lines[7].1.contains("message 17"));
+    }
+}
@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::helpers::benchmark::{score_project, BenchmarkScore, ExpectedProperties};
+use crate::helpers::events;
+use crate::helpers::general::read_api_endpoints;
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::agents_manager::managing_agent::ManagingAgent;
+use crate::models::general::app_context::AppContext;
+use crate::models::general::config::{AppConfig, ConfigError};
+use crate::models::general::run_metrics::MetricsSnapshot;
+
+pub const EVAL_SUBCOMMAND: &str = "eval";
+// Version of the prompts without any version dir: the templates on disk, then the ai_functions
+pub const BUILTIN_PROMPT_VERSION: &str = "builtin";
+
+type EvalError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalProject {
+    pub name: String,
+    // What a user would type at the first question
+    pub request: String,
+    #[serde(default)]
+    pub expected: ExpectedProperties,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalSuite {
+    pub projects: Vec<EvalProject>,
+}
+
+impl EvalSuite {
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Read {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+}
+
+// eval <suite.toml> [--baseline <version>] [--candidate <version>] [--report <file>], other flags go to the config
+// Without --candidate the suite runs once, as a benchmark of the configured prompts
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalArgs {
+    pub suite: PathBuf,
+    pub baseline: Option<String>,
+    pub candidate: Option<String>,
+    pub report: Option<PathBuf>,
+    pub config_args: Vec<String>,
+}
+
+impl EvalArgs {
+    pub fn parse(args: &[String]) -> Result<Self, ConfigError> {
+        let mut suite: Option<PathBuf> = None;
+        let mut baseline: Option<String> = None;
+        let mut candidate: Option<String> = None;
+        let mut report: Option<PathBuf> = None;
+        let mut config_args = Vec::new();
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = |flag: &str| {
+                inline_value
+                    .clone()
+                    .or_else(|| args_iter.next().cloned())
+                    .ok_or_else(|| ConfigError::Cli(format!("{} needs a value", flag)))
+            };
+
+            match flag {
+                "--baseline" => baseline = Some(value(flag)?),
+                "--candidate" => candidate = Some(value(flag)?),
+                "--report" => report = Some(PathBuf::from(value(flag)?)),
+                _ if !arg.starts_with("--") && suite.is_none() => suite = Some(PathBuf::from(arg)),
+                _ => config_args.push(arg.clone()),
+            }
+        }
+
+        Ok(Self {
+            suite: suite.ok_or_else(|| ConfigError::Cli("eval needs a suite file".to_string()))?,
+            baseline,
+            candidate,
+            report,
+            config_args,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectOutcome {
+    pub project: String,
+    pub version: String,
+    pub is_completed: bool,
+    pub error: Option<String>,
+    pub metrics: MetricsSnapshot,
+    // None when the project has no expected properties
+    pub score: Option<f64>,
+    pub failed_checks: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VersionSummary {
+    pub version: String,
+    pub projects: usize,
+    // Projects with at least one successful build
+    pub compile_success_rate: f64,
+    pub mean_fix_rounds: f64,
+    // Over every endpoint check of every project
+    pub endpoint_pass_rate: f64,
+    pub mean_estimated_tokens: f64,
+    // Mean benchmark score of the scored projects: the regression number of the whole system
+    pub mean_score: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EvalReport {
+    pub summaries: Vec<VersionSummary>,
+    pub projects: Vec<ProjectOutcome>,
+}
+
+fn ratio(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 / total as f64
+}
+
+pub fn summarize(version: &str, outcomes: &[ProjectOutcome]) -> VersionSummary {
+    let projects = outcomes.len();
+    let sum = |field: fn(&MetricsSnapshot) -> usize| -> usize {
+        outcomes.iter().map(|outcome| field(&outcome.metrics)).sum()
+    };
+    let compiled = outcomes
+        .iter()
+        .filter(|outcome| outcome.metrics.successful_builds > 0)
+        .count();
+    let scores: Vec<f64> = outcomes
+        .iter()
+        .filter_map(|outcome| outcome.score)
+        .collect();
+
+    VersionSummary {
+        version: version.to_string(),
+        projects,
+        compile_success_rate: ratio(compiled, projects),
+        mean_fix_rounds: ratio(sum(|metrics| metrics.fix_rounds), projects),
+        endpoint_pass_rate: ratio(
+            sum(|metrics| metrics.endpoint_passes),
+            sum(|metrics| metrics.endpoint_checks),
+        ),
+        mean_estimated_tokens: ratio(sum(|metrics| metrics.estimated_tokens), projects),
+        mean_score: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+    }
+}
+
+// Nobody answers during an eval: the code runs unless the security review blocks it
+fn eval_config(base: &AppConfig, version: &str) -> AppConfig {
+    let mut config = base.clone();
+    config.prompt_version = (version != BUILTIN_PROMPT_VERSION).then(|| version.to_string());
+    config.is_unattended = true;
+    config.approval_policy = config.approval_policy.unattended();
+    // Recorded responses never expire
+    if config.is_replay_only {
+        config.cache_ttl = Duration::MAX;
+    }
+    config
+}
+
+fn panic_message(e: tokio::task::JoinError) -> String {
+    match e.try_into_panic() {
+        Ok(panic) => panic
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| {
+                panic
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+            })
+            .unwrap_or_else(|| "agent panicked".to_string()),
+        Err(e) => e.to_string(),
+    }
+}
+
+fn project_outcome(
+    project: &EvalProject,
+    version: &str,
+    error: Option<String>,
+    metrics: MetricsSnapshot,
+    score: BenchmarkScore,
+) -> ProjectOutcome {
+    ProjectOutcome {
+        project: project.name.clone(),
+        version: version.to_string(),
+        is_completed: error.is_none(),
+        error,
+        metrics,
+        score: score.value(),
+        failed_checks: score
+            .failed_checks()
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+// A failing or panicking project is an outcome like any other, the suite goes on
+async fn run_project(config: AppConfig, project: &EvalProject, version: &str) -> ProjectOutcome {
+    let context = match AppContext::new(config) {
+        Ok(context) => Arc::new(context),
+        Err(e) => {
+            let score = score_project(&project.expected, None, &[], false);
+            return project_outcome(
+                project,
+                version,
+                Some(e.to_string()),
+                MetricsSnapshot::default(),
+                score,
+            );
+        }
+    };
+
+    // The FactSheet is kept even when the project fails, a panic loses it
+    let request = project.request.clone();
+    let task_context = context.clone();
+    let task_res = tokio::spawn(events::in_run(context.run_id, async move {
+        let mut manager = ManagingAgent::new(task_context, request).await?;
+        let project_res = manager.execute_project().await;
+        Ok::<(FactSheet, _), Box<dyn std::error::Error + Send + Sync>>((
+            manager.factsheet().clone(),
+            project_res.map_err(|e| e.to_string()),
+        ))
+    }))
+    .await;
+
+    let (factsheet, error) = match task_res {
+        Ok(Ok((factsheet, project_res))) => (Some(factsheet), project_res.err()),
+        Ok(Err(e)) => (None, Some(e.to_string())),
+        Err(e) => (None, Some(panic_message(e))),
+    };
+    let metrics = context.metrics.snapshot();
+    // The schema file on disk belongs to this run only once the backend wrote it to the FactSheet
+    let generated_routes = match &factsheet {
+        Some(factsheet) if factsheet.api_endpoint_schema.is_some() => read_api_endpoints(),
+        _ => Vec::new(),
+    };
+    let score = score_project(
+        &project.expected,
+        factsheet.as_ref(),
+        &generated_routes,
+        metrics.successful_builds > 0,
+    );
+    project_outcome(project, version, error, metrics, score)
+}
+
+// Runs the whole suite once per prompt version, the baseline first
+pub async fn run_eval(args: &EvalArgs) -> Result<EvalReport, EvalError> {
+    let suite = EvalSuite::from_file(&args.suite)?;
+    let base_config = AppConfig::load(&args.config_args)?;
+    // The baseline defaults to the configured prompts
+    let baseline = args.baseline.clone().unwrap_or_else(|| {
+        base_config
+            .prompt_version
+            .clone()
+            .unwrap_or_else(|| BUILTIN_PROMPT_VERSION.to_string())
+    });
+
+    let mut outcomes: Vec<ProjectOutcome> = Vec::new();
+    let mut summaries: Vec<VersionSummary> = Vec::new();
+    for version in std::iter::once(&baseline).chain(&args.candidate) {
+        let config = eval_config(&base_config, version);
+        config.validate()?;
+        let mut version_outcomes = Vec::new();
+        for project in &suite.projects {
+            version_outcomes.push(run_project(config.clone(), project, version).await);
+        }
+        summaries.push(summarize(version, &version_outcomes));
+        outcomes.extend(version_outcomes);
+    }
+
+    let eval_report = EvalReport {
+        summaries,
+        projects: outcomes,
+    };
+    if let Some(report) = &args.report {
+        std::fs::write(report, serde_json::to_string_pretty(&eval_report)?)?;
+    }
+    Ok(eval_report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_security::ApprovalPolicy;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn outcome(metrics: MetricsSnapshot) -> ProjectOutcome {
+        ProjectOutcome {
+            project: "todo".to_string(),
+            version: "v2".to_string(),
+            is_completed: true,
+            error: None,
+            metrics,
+            score: None,
+            failed_checks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_eval_args_split_config_flags() {
+        let eval_args = EvalArgs::parse(&args(&[
+            "evals/suite.toml",
+            "--candidate=v2",
+            "--recorded",
+            "--model",
+            "gpt-4o",
+            "--report",
+            "report.json",
+        ]))
+        .unwrap();
+        assert_eq!(
+            eval_args,
+            EvalArgs {
+                suite: PathBuf::from("evals/suite.toml"),
+                baseline: None,
+                candidate: Some("v2".to_string()),
+                report: Some(PathBuf::from("report.json")),
+                config_args: args(&["--recorded", "--model", "gpt-4o"]),
+            }
+        );
+        assert!(EvalArgs::parse(&args(&["--candidate", "v2"])).is_err());
+    }
+
+    #[test]
+    fn test_summarize_rates() {
+        let mut compiled = outcome(MetricsSnapshot {
+            estimated_tokens: 3000,
+            builds: 2,
+            successful_builds: 1,
+            fix_rounds: 1,
+            endpoint_checks: 4,
+            endpoint_passes: 3,
+            ..MetricsSnapshot::default()
+        });
+        compiled.score = Some(1.0);
+        let mut broken = outcome(MetricsSnapshot {
+            estimated_tokens: 1000,
+            builds: 3,
+            fix_rounds: 2,
+            ..MetricsSnapshot::default()
+        });
+        broken.score = Some(0.5);
+        let unscored = outcome(MetricsSnapshot::default());
+
+        let summary = summarize("v2", &[compiled, broken, unscored]);
+        assert_eq!(summary.projects, 3);
+        assert_eq!(summary.compile_success_rate, 1.0 / 3.0);
+        assert_eq!(summary.mean_fix_rounds, 1.0);
+        assert_eq!(summary.endpoint_pass_rate, 0.75);
+        assert_eq!(summary.mean_estimated_tokens, 4000.0 / 3.0);
+        assert_eq!(summary.mean_score, Some(0.75));
+        assert_eq!(summarize("v1", &[]).compile_success_rate, 0.0);
+        assert_eq!(summarize("v1", &[]).mean_score, None);
+    }
+
+    #[test]
+    fn test_example_suite_is_valid() {
+        let suite = EvalSuite::from_file(Path::new("evals/suite.toml")).unwrap();
+        assert!(!suite.projects.is_empty());
+        assert!(suite
+            .projects
+            .iter()
+            .all(|project| project.expected.must_compile));
+
+        let config = eval_config(&AppConfig::default(), "v2");
+        assert_eq!(config.prompt_version.as_deref(), Some("v2"));
+        assert_eq!(config.approval_policy, ApprovalPolicy::Strict);
+        assert!(config.is_unattended);
+        assert_eq!(
+            eval_config(&config, BUILTIN_PROMPT_VERSION).prompt_version,
+            None
+        );
+    }
+}
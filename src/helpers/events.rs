@@ -0,0 +1,317 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::helpers::command_line::PrintCommand;
+use crate::models::agent_basic::basic_agent::AgentState;
+use crate::models::general::llm::Message;
+
+// One JSON event per line, in the run dir
+pub const EVENT_LOG_FILE: &str = "events.jsonl";
+// The FactSheet as the run left it, next to the event log
+pub const RUN_FACTSHEET_FILE: &str = "factsheet.json";
+
+// Everything an agent does during a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum RunEvent {
+    // The answer to the first question, what a replay starts from
+    RunStarted {
+        user_request: String,
+    },
+    AgentMessage {
+        agent: String,
+        kind: PrintCommand,
+        message: String,
+    },
+    StateChanged {
+        agent: String,
+        state: AgentState,
+    },
+    LlmCall {
+        agent: String,
+        function: String,
+        prompt: Vec<Message>,
+        response: String,
+        estimated_tokens: usize,
+        latency_ms: u64,
+        attempts: u32,
+    },
+    BackendCode {
+        agent: String,
+        code: String,
+    },
+    Build {
+        agent: String,
+        is_success: bool,
+        errors: String,
+    },
+    EndpointTest {
+        agent: String,
+        route: String,
+        has_credentials: bool,
+        expected_status: u16,
+        // None when the server did not answer
+        status: Option<u16>,
+    },
+    UserApproval {
+        agent: String,
+        is_approved: bool,
+        // Approved without asking, see --yes
+        is_unattended: bool,
+    },
+    // Any other answer of the user, a replay reads it back instead of asking again
+    UserInput {
+        prompt: String,
+        answer: serde_json::Value,
+    },
+}
+
+// Tells apart the runs of one process, e.g. the projects of an eval, see AppContext::run_id
+pub type RunId = u64;
+
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    // Run of the events emitted by the current task, see in_run
+    static CURRENT_RUN: RunId;
+}
+
+pub fn new_run_id() -> RunId {
+    NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+// Events emitted while the future runs belong to run_id.
+// Not inherited by spawned tasks, which are wrapped in turn
+pub async fn in_run<F: Future>(run_id: RunId, future: F) -> F::Output {
+    CURRENT_RUN.scope(run_id, future).await
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRecord {
+    // Milliseconds since the unix epoch
+    pub timestamp_ms: u64,
+    // None outside of any run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<RunId>,
+    #[serde(flatten)]
+    pub event: RunEvent,
+}
+
+pub trait EventSubscriber: Send + Sync + std::fmt::Debug {
+    fn on_event(&self, record: &EventRecord);
+}
+
+// The terminal view, the event log, ... registered once at startup
+static SUBSCRIBERS: Mutex<Vec<Arc<dyn EventSubscriber>>> = Mutex::new(Vec::new());
+
+pub fn subscribe(subscriber: Arc<dyn EventSubscriber>) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(subscriber);
+}
+
+pub fn unsubscribe(subscriber: &Arc<dyn EventSubscriber>) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|registered| !Arc::ptr_eq(registered, subscriber));
+}
+
+// Every subscriber sees every event, in the order they were emitted by one task
+pub fn emit(event: RunEvent) {
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default();
+    let record = EventRecord {
+        timestamp_ms,
+        run_id: CURRENT_RUN.try_with(|run_id| *run_id).ok(),
+        event,
+    };
+    // Not locked while the subscribers run, one of them may emit in turn
+    let subscribers: Vec<Arc<dyn EventSubscriber>> = SUBSCRIBERS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    for subscriber in subscribers {
+        subscriber.on_event(&record);
+    }
+}
+
+// Writes the events of one run to <run dir>/events.jsonl,
+// flushed line by line so a crash keeps the log
+#[derive(Debug)]
+pub struct EventLog {
+    writer: Mutex<BufWriter<File>>,
+    run_id: RunId,
+}
+
+impl EventLog {
+    pub fn create(run_dir: &Path, run_id: RunId) -> std::io::Result<Self> {
+        std::fs::create_dir_all(run_dir)?;
+        let file = File::create(run_dir.join(EVENT_LOG_FILE))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+            run_id,
+        })
+    }
+}
+
+impl EventSubscriber for EventLog {
+    fn on_event(&self, record: &EventRecord) {
+        // Other runs of the process emit on the same bus
+        if record.run_id != Some(self.run_id) {
+            return;
+        }
+        let line = serde_json::to_string(record).expect("Events are always valid JSON");
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        // A full disk must not stop the run
+        writeln!(writer, "{}", line)
+            .and_then(|_| writer.flush())
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Collector {
+        records: Mutex<Vec<EventRecord>>,
+    }
+
+    impl EventSubscriber for Collector {
+        fn on_event(&self, record: &EventRecord) {
+            self.records.lock().unwrap().push(record.clone());
+        }
+    }
+
+    fn events_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("{}_{}", name, std::process::id()))
+    }
+
+    fn logged_records(run_dir: &Path) -> Vec<EventRecord> {
+        std::fs::read_to_string(run_dir.join(EVENT_LOG_FILE))
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_events_reach_subscribers_and_the_log() {
+        let run_dir = events_dir("events");
+        let run_id = new_run_id();
+        let event_log: Arc<dyn EventSubscriber> =
+            Arc::new(EventLog::create(&run_dir, run_id).unwrap());
+        let collector = Arc::new(Collector::default());
+        let collector_subscriber: Arc<dyn EventSubscriber> = collector.clone();
+        subscribe(event_log.clone());
+        subscribe(collector_subscriber.clone());
+
+        // Other tests emit at the same time, only the events of this agent are checked
+        let agent = "Events Test Agent".to_string();
+        in_run(run_id, async {
+            PrintCommand::Issue.print_agent_message(&agent, "build failed");
+            emit(RunEvent::EndpointTest {
+                agent: agent.clone(),
+                route: "/todos".to_string(),
+                has_credentials: false,
+                expected_status: 200,
+                status: None,
+            });
+        })
+        .await;
+        unsubscribe(&event_log);
+        unsubscribe(&collector_subscriber);
+        emit(RunEvent::StateChanged {
+            agent: agent.clone(),
+            state: AgentState::Finished,
+        });
+
+        let is_test_event = |record: &&EventRecord| match &record.event {
+            RunEvent::AgentMessage { agent: a, .. } | RunEvent::EndpointTest { agent: a, .. } => {
+                *a == agent
+            }
+            RunEvent::StateChanged { agent: a, .. } => *a == agent,
+            _ => false,
+        };
+        let collected = collector.records.lock().unwrap();
+        let collected: Vec<&EventRecord> = collected.iter().filter(is_test_event).collect();
+        assert_eq!(collected.len(), 2);
+        assert_eq!(
+            collected[0].event,
+            RunEvent::AgentMessage {
+                agent: agent.clone(),
+                kind: PrintCommand::Issue,
+                message: "build failed".to_string(),
+            }
+        );
+
+        let log = std::fs::read_to_string(run_dir.join(EVENT_LOG_FILE)).unwrap();
+        let logged: Vec<EventRecord> = logged_records(&run_dir);
+        let logged: Vec<&EventRecord> = logged.iter().filter(is_test_event).collect();
+        assert_eq!(logged, collected);
+        assert!(log.contains(r#""event":"endpoint_test""#));
+        assert_eq!(collected[0].run_id, Some(run_id));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_runs_log_only_their_own_events() {
+        let (first_dir, second_dir) = (events_dir("events_first"), events_dir("events_second"));
+        let (first_run, second_run) = (new_run_id(), new_run_id());
+        let first_log: Arc<dyn EventSubscriber> =
+            Arc::new(EventLog::create(&first_dir, first_run).unwrap());
+        let second_log: Arc<dyn EventSubscriber> =
+            Arc::new(EventLog::create(&second_dir, second_run).unwrap());
+        subscribe(first_log.clone());
+        subscribe(second_log.clone());
+
+        let project = |user_request: &'static str| async move {
+            emit(RunEvent::RunStarted {
+                user_request: user_request.to_string(),
+            });
+            // Like the agents spawned by the manager
+            tokio::spawn(in_run(CURRENT_RUN.get(), async move {
+                tokio::task::yield_now().await;
+                emit(RunEvent::BackendCode {
+                    agent: "Backend Developer".to_string(),
+                    code: user_request.to_string(),
+                });
+            }))
+            .await
+            .unwrap();
+        };
+        tokio::join!(
+            in_run(first_run, project("a todo api")),
+            in_run(second_run, project("a weather site")),
+        );
+        unsubscribe(&first_log);
+        unsubscribe(&second_log);
+
+        for (run_dir, run_id, user_request) in [
+            (&first_dir, first_run, "a todo api"),
+            (&second_dir, second_run, "a weather site"),
+        ] {
+            let logged = logged_records(run_dir);
+            assert_eq!(logged.len(), 2);
+            assert!(logged.iter().all(|record| record.run_id == Some(run_id)));
+            assert_eq!(
+                logged[0].event,
+                RunEvent::RunStarted {
+                    user_request: user_request.to_string(),
+                }
+            );
+        }
+    }
+}
@@ -1,9 +1,21 @@
+use futures::stream::{self, StreamExt};
 use reqwest::Client;
+use schemars::gen::SchemaSettings;
+use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::future::Future;
+use std::time::Instant;
+use tracing::{field, instrument, Instrument, Span};
 
-use crate::apis::call_request::call_gpt;
-use crate::helpers::command_line::PrintCommand;
+use crate::ai_functions::aifunc_memory::summarize_agent_memory;
+use crate::apis::call_request::{retry_jitter, LlmError, ModelSettings, StructuredOutput};
+use crate::helpers::command_line::{PrintCommand, StreamOutput, StreamPrinter};
+use crate::helpers::events::{self, RunEvent};
+use crate::helpers::prompts::PromptInput;
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agents::agent_traits::RouteObject;
+use crate::models::general::app_context::AppContext;
 use crate::models::general::llm::Message;
 
 const CODE_TEMPLATE_PATH: &str = "../web_template/src/code_template.rs";
@@ -13,12 +25,29 @@ const EXEC_MAIN_PATH: &str = "../web_template/src/main.rs";
 pub const WEB_SERVER_PROJECT_PATH: &str = "../web_template/";
 // const API_SCHEMA_PATH: &str = "/home/hungson175/dev/rust_autogpt/auto_gippity/schemas/api_schema.json";
 const API_SCHEMA_PATH: &str = "../web_template/api_schema.json";
+const WEB_SERVER_CARGO_TOML_PATH: &str = "../web_template/Cargo.toml";
+const PERSISTENCE_MODULE_PATH: &str = "../web_template/src/db.rs";
+pub const MIGRATIONS_PATH: &str = "../web_template/migrations/0001_init.sql";
+// Path of the migrations as seen from the persistence module, used with include_str!
+pub const MIGRATIONS_INCLUDE_PATH: &str = "../migrations/0001_init.sql";
+pub const DATABASE_VERIFY_PATH: &str = "../web_template/data/verify.db";
+const AUTH_MODULE_PATH: &str = "../web_template/src/auth.rs";
 
-// Extend ai function to encourage specific output
-pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -> Message {
-    let ai_function_str = ai_func(func_input);
-    // dbg!(ai_function_str);
+// Vetted authentication module injected into projects with user login and logout
+pub const AUTH_MODULE: &str = include_str!("../../templates/auth.rs");
+pub const AUTH_DEPENDENCIES: [&str; 2] = [
+    r#"argon2 = "0.5.3""#,
+    r#"rand_core = { version = "0.6.4", features = ["getrandom"] }"#,
+];
+// Upper bound of simultaneous HTTP checks (external urls, endpoints)
+pub const MAX_CONCURRENT_REQUESTS: usize = 8;
+const AUTH_TEST_USERNAME: &str = "auto_gippity_tester";
+const AUTH_TEST_PASSWORD: &str = "auto_gippity_password";
+// Function arguments are always an object: the structured output is its only field
+const STRUCTURED_RESULT_FIELD: &str = "result";
 
+// Extend ai function to encourage specific output
+pub fn extend_ai_function(ai_function_str: &str, func_input: &str) -> Message {
     // Extend the string to encourage only printing the output
     let msg: String = format!(
         "FUNCTION: {}\n
@@ -35,38 +64,328 @@ pub fn extend_ai_function(ai_func: fn(&str) -> &'static str, func_input: &str) -
     }
 }
 
+// Same function, but the model returns the result as the arguments of a function call
+pub fn extend_ai_function_as_tool(
+    ai_function_str: &str,
+    func_input: &str,
+    function_name: &str,
+) -> Message {
+    let msg: String = format!(
+        "FUNCTION: {}\n
+        INPUT: {}\n
+    INSTRUCTION: Work out what the function will return with the given input.
+    Call {} with it as \"{}\", following the format described by the function.",
+        ai_function_str, func_input, function_name, STRUCTURED_RESULT_FIELD
+    );
+
+    Message {
+        role: "system".to_string(),
+        content: msg,
+    }
+}
+
+// JSON Schema of T, with every definition inlined so it can be nested in the arguments object
+pub fn structured_output_for<T: JsonSchema>(function_name: &str) -> StructuredOutput {
+    let generator = SchemaSettings::draft07()
+        .with(|settings| {
+            settings.inline_subschemas = true;
+            settings.meta_schema = None;
+        })
+        .into_generator();
+    let result_schema = generator.into_root_schema_for::<T>().schema;
+    StructuredOutput {
+        name: function_name.to_string(),
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": { STRUCTURED_RESULT_FIELD: result_schema },
+            "required": [STRUCTURED_RESULT_FIELD],
+        }),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct StructuredResult<T> {
+    result: T,
+}
+
+// The prompt is the template of the ai_function in the prompt library, its doc comment otherwise
+fn prompt_message(
+    context: &AppContext,
+    input: &PromptInput,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+    structured_output: Option<&StructuredOutput>,
+) -> Message {
+    let builtin = function_pass(&input.to_text());
+    let prompt = context.prompts.render(agent_operation, builtin, input);
+    match structured_output {
+        Some(structured_output) => {
+            extend_ai_function_as_tool(&prompt.function, &prompt.input, &structured_output.name)
+        }
+        None => extend_ai_function(&prompt.function, &prompt.input),
+    }
+}
+
 pub async fn ai_task_request(
-    msg_context: String,
+    context: &AppContext,
+    input: PromptInput,
     agent_position: &str,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> String {
-    let extended_msg: Message = extend_ai_function(function_pass, &msg_context);
+) -> Result<String, LlmError> {
+    let extended_msg: Message =
+        prompt_message(context, &input, agent_operation, function_pass, None);
 
     PrintCommand::AICall.print_agent_message(agent_position, agent_operation);
+    call_gpt_with_retry(
+        context,
+        vec![extended_msg],
+        agent_position,
+        agent_operation,
+        None,
+        false,
+    )
+    .await
+}
 
-    let llm_response_res: Result<String, Box<dyn std::error::Error + Send>> =
-        call_gpt(vec![extended_msg.clone()]).await;
+// Retries the errors which can go away, see RetryPolicy, and returns the others,
+// the agent maps them to an AgentError
+#[instrument(
+    name = "llm.call",
+    skip_all,
+    fields(
+        agent = agent_position,
+        function = function_name,
+        attempts = field::Empty,
+        estimated_tokens = field::Empty
+    )
+)]
+async fn call_gpt_with_retry(
+    context: &AppContext,
+    messages: Vec<Message>,
+    agent_position: &str,
+    function_name: &str,
+    structured_output: Option<&StructuredOutput>,
+    is_cache_bypassed: bool,
+) -> Result<String, LlmError> {
+    let retry_policy = context.config.retry_policy;
+    let mut settings = context.config.model_for(agent_position, function_name);
+    settings.is_cache_bypassed = is_cache_bypassed;
+    let mut attempt: u32 = 1;
+    loop {
+        let started = Instant::now();
+        let llm_response_res = match &context.replay {
+            Some(replay) => replay.next(agent_position, function_name).ok_or_else(|| {
+                LlmError::NotRecorded(format!("{} of {}", function_name, agent_position))
+            }),
+            None => call_gpt_printed(context, messages.clone(), &settings, structured_output).await,
+        };
+        let error = match llm_response_res {
+            Ok(llm_res_str) => {
+                let estimated_tokens = context.metrics.record_llm_call(&messages, &llm_res_str);
+                Span::current()
+                    .record("attempts", attempt)
+                    .record("estimated_tokens", estimated_tokens);
+                events::emit(RunEvent::LlmCall {
+                    agent: agent_position.to_string(),
+                    function: function_name.to_string(),
+                    prompt: messages,
+                    response: llm_res_str.clone(),
+                    estimated_tokens,
+                    latency_ms: started.elapsed().as_millis() as u64,
+                    attempts: attempt,
+                });
+                return Ok(llm_res_str);
+            }
+            Err(error) => error,
+        };
+        let Some(delay) = retry_policy.delay(attempt, &error, retry_jitter()) else {
+            tracing::error!(%error, attempt, "LLM call failed");
+            return Err(error);
+        };
 
-    match llm_response_res {
-        Ok(llm_res_str) => llm_res_str,
-        Err(_) => call_gpt(vec![extended_msg.clone()])
-            .await
-            .expect("Failed 2 to call OpenAI"),
+        tracing::warn!(%error, attempt, "LLM call failed, retrying");
+        PrintCommand::Issue.print_agent_message(
+            agent_position,
+            &format!(
+                "LLM call failed ({}), retrying in {:.1}s ...",
+                error,
+                delay.as_secs_f64()
+            ),
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
     }
 }
 
-pub async fn ai_task_request_decoded<T: DeserializeOwned>(
-    msg_context: String,
-    agent_position: &str,
+// The response is shown while it streams, see STREAM_OUTPUT
+async fn call_gpt_printed(
+    context: &AppContext,
+    messages: Vec<Message>,
+    settings: &ModelSettings,
+    structured_output: Option<&StructuredOutput>,
+) -> Result<String, LlmError> {
+    let stream_output = context.config.stream_output;
+    if stream_output == StreamOutput::Off {
+        return context
+            .llm
+            .call_gpt(messages, settings, structured_output)
+            .await;
+    }
+    let mut printer = StreamPrinter::new(stream_output);
+    let llm_response_res = context
+        .llm
+        .call_gpt_stream(messages, settings, structured_output, &mut |chunk| {
+            printer.print_chunk(chunk)
+        })
+        .await;
+    printer.finish();
+    llm_response_res
+}
+
+// Like ai_task_request, but the turn is stored in the agent memory
+// With use_history, the earlier turns are sent first so the LLM sees the previous attempts
+pub async fn agent_task_request(
+    context: &AppContext,
+    agent: &mut BasicAgent,
+    input: PromptInput,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+    use_history: bool,
+) -> Result<String, LlmError> {
+    agent_task_request_with(
+        context,
+        agent,
+        input,
+        agent_operation,
+        function_pass,
+        use_history,
+        None,
+    )
+    .await
+}
+
+async fn agent_task_request_with(
+    context: &AppContext,
+    agent: &mut BasicAgent,
+    input: PromptInput,
+    agent_operation: &str,
+    function_pass: for<'a> fn(&'a str) -> &'static str,
+    use_history: bool,
+    structured_output: Option<&StructuredOutput>,
+) -> Result<String, LlmError> {
+    let extended_msg: Message = prompt_message(
+        context,
+        &input,
+        agent_operation,
+        function_pass,
+        structured_output,
+    );
+
+    PrintCommand::AICall.print_agent_message(&agent.position, agent_operation);
+    let mut messages: Vec<Message> = if use_history {
+        agent.memory.clone()
+    } else {
+        Vec::new()
+    };
+    messages.push(extended_msg.clone());
+    let llm_response: String = call_gpt_with_retry(
+        context,
+        messages,
+        &agent.position,
+        agent_operation,
+        structured_output,
+        agent.is_cache_bypassed,
+    )
+    .instrument(agent.state_span.clone())
+    .await?;
+
+    agent.remember(extended_msg, &llm_response);
+    summarize_agent_memory_if_needed(context, agent).await?;
+    Ok(llm_response)
+}
+
+// The answer is asked through tool calling with the JSON Schema of T when the route allows it,
+// through the printer prompt otherwise
+pub async fn agent_task_request_decoded<T: DeserializeOwned + JsonSchema>(
+    context: &AppContext,
+    agent: &mut BasicAgent,
+    input: PromptInput,
     agent_operation: &str,
     function_pass: for<'a> fn(&'a str) -> &'static str,
-) -> T {
-    let llm_response: String =
-        ai_task_request(msg_context, agent_position, agent_operation, function_pass).await;
-    let decoded_response: T =
-        serde_json::from_str(llm_response.as_str()).expect("Failed to decode LLM response");
-    return decoded_response;
+    use_history: bool,
+) -> Result<T, LlmError> {
+    let settings = context.config.model_for(&agent.position, agent_operation);
+    if !settings.uses_tool_calling() {
+        let llm_response: String = agent_task_request(
+            context,
+            agent,
+            input,
+            agent_operation,
+            function_pass,
+            use_history,
+        )
+        .await?;
+        return decode_llm_response(&llm_response);
+    }
+
+    let structured_output = structured_output_for::<T>(agent_operation);
+    let llm_response: String = agent_task_request_with(
+        context,
+        agent,
+        input,
+        agent_operation,
+        function_pass,
+        use_history,
+        Some(&structured_output),
+    )
+    .await?;
+    let decoded_response: StructuredResult<T> = decode_llm_response(&llm_response)?;
+    Ok(decoded_response.result)
+}
+
+// A malformed answer fails the agent like a failed call, the manager asks again without the cache
+fn decode_llm_response<T: DeserializeOwned>(llm_response: &str) -> Result<T, LlmError> {
+    serde_json::from_str(llm_response).map_err(|e| LlmError::InvalidResponse(e.to_string()))
+}
+
+async fn summarize_agent_memory_if_needed(
+    context: &AppContext,
+    agent: &mut BasicAgent,
+) -> Result<(), LlmError> {
+    let Some(turns) = agent.memory_to_summarize() else {
+        return Ok(());
+    };
+    let conversation: String = turns
+        .iter()
+        .map(|message| format!("{}: {}", message.role.to_uppercase(), message.content))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let summary: String = ai_task_request(
+        context,
+        PromptInput::new().with("CONVERSATION", conversation),
+        &agent.position,
+        get_function_string!(summarize_agent_memory),
+        summarize_agent_memory,
+    )
+    .await?;
+    agent.compress_memory(&summary);
+    Ok(())
+}
+
+// Run the futures with at most max_concurrency of them in flight, results keep the input order
+pub async fn run_bounded<F, T>(
+    futures: impl IntoIterator<Item = F>,
+    max_concurrency: usize,
+) -> Vec<T>
+where
+    F: Future<Output = T>,
+{
+    stream::iter(futures)
+        .buffered(max_concurrency.max(1))
+        .collect()
+        .await
 }
 
 // Check whether request url is valid
@@ -75,6 +394,48 @@ pub async fn check_status_code(client: &Client, url: &str) -> Result<u16, reqwes
     Ok(response.status().as_u16())
 }
 
+// Check whether request url is valid when called with (or without) a bearer token
+pub async fn check_status_code_with_token(
+    client: &Client,
+    url: &str,
+    token: Option<&str>,
+) -> Result<u16, reqwest::Error> {
+    let mut request = client.get(url);
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    let response = request.send().await?;
+    Ok(response.status().as_u16())
+}
+
+// Sign up and log in a test user against the injected auth module, returning its session token
+pub async fn login_test_user(client: &Client, base_url: &str) -> Result<String, reqwest::Error> {
+    let credentials = serde_json::json!({
+        "username": AUTH_TEST_USERNAME,
+        "password": AUTH_TEST_PASSWORD,
+    });
+
+    // Signup fails with 409 if the user already exists, login tells whether we are in
+    client
+        .post(format!("{}/auth/signup", base_url))
+        .json(&credentials)
+        .send()
+        .await?;
+
+    let login_response: serde_json::Value = client
+        .post(format!("{}/auth/login", base_url))
+        .json(&credentials)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(login_response["token"]
+        .as_str()
+        .unwrap_or_default()
+        .to_string())
+}
+
 // Get Code Template
 pub fn read_code_template_content() -> String {
     std::fs::read_to_string(CODE_TEMPLATE_PATH).expect("Failed to read code template at ")
@@ -85,51 +446,175 @@ pub fn read_exc_main_content() -> String {
     std::fs::read_to_string(EXEC_MAIN_PATH).expect("Failed to read main code")
 }
 
+// Get every Rust source of the generated web server as (path relative to the project, content)
+pub fn read_web_server_sources() -> Vec<(String, String)> {
+    let src_dir = std::path::Path::new(WEB_SERVER_PROJECT_PATH).join("src");
+    let mut sources: Vec<(String, String)> = Vec::new();
+    let mut dirs = vec![src_dir];
+    while let Some(dir) = dirs.pop() {
+        let entries = std::fs::read_dir(&dir).expect("Failed to read web server sources");
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().map(|ext| ext == "rs").unwrap_or(false) {
+                let content = std::fs::read_to_string(&path).expect("Failed to read source file");
+                let relative = path
+                    .strip_prefix(WEB_SERVER_PROJECT_PATH)
+                    .unwrap_or(&path)
+                    .display()
+                    .to_string();
+                sources.push((relative, content));
+            }
+        }
+    }
+    sources.sort();
+    sources
+}
+
 // Save New Backend Code
 pub fn save_backend_code(content: &str) {
     std::fs::write(EXEC_MAIN_PATH, content).expect("Failed to write main.rs file")
 }
 
+// Save SQLite Migrations
+pub fn save_migrations(migrations: &str) {
+    if let Some(dir) = std::path::Path::new(MIGRATIONS_PATH).parent() {
+        std::fs::create_dir_all(dir).expect("Failed to create migrations directory");
+    }
+    std::fs::write(MIGRATIONS_PATH, migrations).expect("Failed to write migrations file")
+}
+
+// Save Persistence Module (db.rs) next to the backend main.rs
+pub fn save_persistence_module(content: &str) {
+    std::fs::write(PERSISTENCE_MODULE_PATH, content).expect("Failed to write db.rs file")
+}
+
+// Make sure the web server can compile the persistence module
+pub fn ensure_web_server_dependency(dependency_line: &str) {
+    let cargo_toml = std::fs::read_to_string(WEB_SERVER_CARGO_TOML_PATH)
+        .expect("Failed to read web server Cargo.toml");
+    let updated = add_dependency_line(&cargo_toml, dependency_line);
+    if updated != cargo_toml {
+        std::fs::write(WEB_SERVER_CARGO_TOML_PATH, updated)
+            .expect("Failed to write web server Cargo.toml");
+    }
+}
+
+// Insert a dependency right below [dependencies], unless the crate is already listed
+fn add_dependency_line(cargo_toml: &str, dependency_line: &str) -> String {
+    let crate_name = dependency_line.split('=').next().unwrap_or("").trim();
+    let already_listed = cargo_toml
+        .lines()
+        .any(|line| line.split('=').next().unwrap_or("").trim() == crate_name);
+    if already_listed {
+        return cargo_toml.to_string();
+    }
+
+    match cargo_toml.find("[dependencies]") {
+        Some(pos) => {
+            let insert_at = pos + "[dependencies]".len();
+            format!(
+                "{}\n{}{}",
+                &cargo_toml[..insert_at],
+                dependency_line,
+                &cargo_toml[insert_at..]
+            )
+        }
+        None => format!("{}\n[dependencies]\n{}\n", cargo_toml, dependency_line),
+    }
+}
+
+// Save the vetted auth module (auth.rs) next to the backend main.rs
+pub fn save_auth_module() {
+    std::fs::write(AUTH_MODULE_PATH, AUTH_MODULE).expect("Failed to write auth.rs file")
+}
+
 // Save JSON API Endpoint Schema
 pub fn save_api_endpoints(api_endpoints: &str) {
     std::fs::write(API_SCHEMA_PATH, api_endpoints).expect("Failed to write api_schema.json file")
 }
 
+// Every route of the generated project, not only the ones the backend tested
+pub fn read_api_endpoints() -> Vec<RouteObject> {
+    std::fs::read_to_string(API_SCHEMA_PATH)
+        .ok()
+        .and_then(|api_endpoints| serde_json::from_str(&api_endpoints).ok())
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::ai_functions::aifunc_architect::print_project_scope;
     use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
-use std::fs;
+    use crate::models::agents::agent_traits::{AgentError, ProjectScope};
+    use crate::models::general::config::AppConfig;
+    use std::fs;
 
     #[test]
     fn test_extending_ai_function() {
-        let extended_msg = extend_ai_function(convert_user_input_to_goal, "dummy variable");
+        let extended_msg = extend_ai_function(
+            convert_user_input_to_goal("dummy variable"),
+            "dummy variable",
+        );
         dbg!(&extended_msg);
         assert_eq!(extended_msg.role, "system".to_string());
     }
 
-    #[tokio::test]
-    async fn test_ai_task_request() {
-        // Arrange
-        let msg_context = "Build me a webserver for making Bitcoin price api requests".to_string();
-        let agent_position = "Managing Agent";
-        let agent_operation = "Defining user requirements";
-        let function_pass = convert_user_input_to_goal;
+    #[test]
+    fn test_truncated_response_is_a_retryable_error() {
+        let truncated = r#"{"result": {"is_crud_required": true, "is_user_lo"#;
+        let err = decode_llm_response::<StructuredResult<ProjectScope>>(truncated).unwrap_err();
+        assert!(matches!(err, LlmError::InvalidResponse(_)));
+        assert!(matches!(
+            AgentError::from(err),
+            AgentError::Retryable(message) if message.starts_with("LLM call failed: invalid response")
+        ));
+    }
 
-        // Act
-        // let response = ai_task_request(msg_context.to_string(), agent_position, agent_operation, function_pass).await;
-        // dbg!(&response);
+    #[test]
+    fn test_structured_output_inlines_the_result_schema() {
+        let structured_output =
+            structured_output_for::<Vec<RouteObject>>("print_rest_api_endpoints");
+        assert_eq!(structured_output.name, "print_rest_api_endpoints");
+        let result = &structured_output.schema["properties"][STRUCTURED_RESULT_FIELD];
+        assert_eq!(result["type"], "array");
+        assert_eq!(result["items"]["properties"]["route"]["type"], "string");
+        assert_eq!(
+            result["items"]["properties"]["response"],
+            serde_json::json!({})
+        );
 
+        let scope = structured_output_for::<ProjectScope>("print_project_scope");
+        let schema = scope.schema.to_string();
+        assert!(schema.contains("is_crud_required"));
+        assert!(!schema.contains("$ref") && !schema.contains("$schema"));
+
+        let decoded: StructuredResult<ProjectScope> = serde_json::from_str(
+            r#"{"result": {"is_crud_required": true, "is_user_login_and_logout": false, "is_external_urls_required": false}}"#,
+        )
+        .unwrap();
+        assert!(decoded.result.is_crud_required);
+    }
+
+    #[tokio::test]
+    async fn test_ai_task_request() {
+        let context = AppContext::new(AppConfig::load(&[]).expect("Invalid configuration"))
+            .expect("Failed to create context");
         let arch_response = ai_task_request(
-            "build a website that makes Bitcoin price API requests".to_string(),
+            &context,
+            PromptInput::new().with(
+                "PROJECT_DESCRIPTION",
+                "build a website that makes Bitcoin price API requests",
+            ),
             "Architect Agent",
             "Build architect requirements",
             print_project_scope,
         )
-        .await;
+        .await
+        .expect("Failed to call OpenAI");
         dbg!(&arch_response);
-        assert!(true);
     }
 
     #[test]
@@ -144,9 +629,47 @@ use std::fs;
         save_api_endpoints(api_endpoints);
 
         // Assert
-        let saved_endpoints = fs::read_to_string(API_SCHEMA_PATH).expect("Failed to read api_schema.json file");
+        let saved_endpoints =
+            fs::read_to_string(API_SCHEMA_PATH).expect("Failed to read api_schema.json file");
         assert_eq!(saved_endpoints, api_endpoints);
     }
 
-}
+    #[tokio::test]
+    async fn test_run_bounded_keeps_order_and_bound() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
 
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let results = run_bounded(
+            (0..10u64).map(|i| {
+                let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+                async move {
+                    let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(10 - i)).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    i
+                }
+            }),
+            3,
+        )
+        .await;
+
+        assert_eq!(results, (0..10).collect::<Vec<u64>>());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_add_dependency_line() {
+        let cargo_toml = "[package]\nname = \"web_template\"\n\n[dependencies]\nserde = \"1.0\"\n";
+        let rusqlite_line = r#"rusqlite = { version = "0.31.0", features = ["bundled"] }"#;
+
+        let updated = add_dependency_line(cargo_toml, rusqlite_line);
+        assert!(updated.contains("[dependencies]\nrusqlite = "));
+        assert!(updated.contains("serde = \"1.0\""));
+
+        // Adding it twice is a no-op
+        assert_eq!(add_dependency_line(&updated, rusqlite_line), updated);
+    }
+}
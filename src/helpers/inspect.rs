@@ -0,0 +1,485 @@
+use similar::TextDiff;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::helpers::events::{EventRecord, RunEvent, EVENT_LOG_FILE, RUN_FACTSHEET_FILE};
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::general::config::ConfigError;
+
+pub const INSPECT_SUBCOMMAND: &str = "inspect";
+pub const REPLAY_SUBCOMMAND: &str = "replay";
+// Lines of a prompt, response or build error shown without --full
+const PREVIEW_LINES: usize = 12;
+
+#[derive(Debug)]
+pub enum InspectError {
+    Read {
+        path: PathBuf,
+        message: String,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    NoUserRequest(PathBuf),
+}
+
+impl std::fmt::Display for InspectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InspectError::Read { path, message } => {
+                write!(f, "cannot read {}: {}", path.display(), message)
+            }
+            InspectError::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{} line {}: {}", path.display(), line, message),
+            InspectError::NoUserRequest(path) => {
+                write!(f, "{} does not record the user request", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for InspectError {}
+
+// The event log and the final FactSheet of one run dir
+#[derive(Debug)]
+pub struct RunRecord {
+    pub run_dir: PathBuf,
+    pub events: Vec<EventRecord>,
+    // None when the run stopped before writing it
+    pub factsheet: Option<FactSheet>,
+}
+
+impl RunRecord {
+    pub fn load(run_dir: &Path) -> Result<Self, InspectError> {
+        let path = run_dir.join(EVENT_LOG_FILE);
+        let log = std::fs::read_to_string(&path).map_err(|e| InspectError::Read {
+            path: path.clone(),
+            message: e.to_string(),
+        })?;
+        let lines = log
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        let mut events = Vec::new();
+        for (position, (index, line)) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(record) => events.push(record),
+                // The last line may be cut short when the run was killed
+                Err(_) if position + 1 == lines.len() => {}
+                Err(e) => {
+                    return Err(InspectError::Parse {
+                        path: path.clone(),
+                        line: index + 1,
+                        message: e.to_string(),
+                    })
+                }
+            }
+        }
+
+        let factsheet = std::fs::read_to_string(run_dir.join(RUN_FACTSHEET_FILE))
+            .ok()
+            .and_then(|factsheet| serde_json::from_str(&factsheet).ok());
+        Ok(Self {
+            run_dir: run_dir.to_path_buf(),
+            events,
+            factsheet,
+        })
+    }
+
+    pub fn user_request(&self) -> Result<&str, InspectError> {
+        self.events
+            .iter()
+            .find_map(|record| match &record.event {
+                RunEvent::RunStarted { user_request } => Some(user_request.as_str()),
+                _ => None,
+            })
+            .ok_or_else(|| InspectError::NoUserRequest(self.run_dir.clone()))
+    }
+}
+
+// inspect <run-dir> [--full]
+// replay <run-dir> [config flags], e.g. --yes to replay the recorded run unattended
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunDirArgs {
+    pub run_dir: PathBuf,
+    pub is_full: bool,
+    pub config_args: Vec<String>,
+}
+
+impl RunDirArgs {
+    pub fn parse(subcommand: &str, args: &[String]) -> Result<Self, ConfigError> {
+        let mut run_dir: Option<PathBuf> = None;
+        let mut is_full = false;
+        let mut config_args = Vec::new();
+        for arg in args {
+            match arg.as_str() {
+                "--full" if subcommand == INSPECT_SUBCOMMAND => is_full = true,
+                _ if arg.starts_with("--") || run_dir.is_some() => config_args.push(arg.clone()),
+                _ => run_dir = Some(PathBuf::from(arg)),
+            }
+        }
+        if subcommand == INSPECT_SUBCOMMAND && !config_args.is_empty() {
+            return Err(ConfigError::Cli(format!(
+                "unknown argument {} for inspect",
+                config_args[0]
+            )));
+        }
+        let run_dir =
+            run_dir.ok_or_else(|| ConfigError::Cli(format!("usage: {} <run-dir>", subcommand)))?;
+        Ok(Self {
+            run_dir,
+            is_full,
+            config_args,
+        })
+    }
+}
+
+fn preview(text: &str, is_full: bool) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    if is_full || lines.len() <= PREVIEW_LINES {
+        return text.to_string();
+    }
+    format!(
+        "{}\n... {} more lines, see --full",
+        lines[..PREVIEW_LINES].join("\n"),
+        lines.len() - PREVIEW_LINES
+    )
+}
+
+fn indented(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// Timeline of the agent states, every LLM call, the code versions as diffs,
+// the build errors of every round and the endpoint results
+pub fn render_inspection(run: &RunRecord, is_full: bool) -> String {
+    let mut out = String::new();
+    let started_ms = run.events.first().map_or(0, |record| record.timestamp_ms);
+    let elapsed =
+        |record: &EventRecord| (record.timestamp_ms.saturating_sub(started_ms)) as f64 / 1000.0;
+
+    writeln!(out, "Run {}", run.run_dir.display()).unwrap();
+    if let Ok(user_request) = run.user_request() {
+        writeln!(out, "Request: {}", user_request).unwrap();
+    }
+
+    writeln!(out, "\n== Agent states").unwrap();
+    for record in &run.events {
+        if let RunEvent::StateChanged { agent, state } = &record.event {
+            writeln!(out, "{:>8.1}s  {:<22} {:?}", elapsed(record), agent, state).unwrap();
+        }
+    }
+
+    writeln!(out, "\n== LLM calls").unwrap();
+    let llm_calls = run.events.iter().filter_map(|record| match &record.event {
+        RunEvent::LlmCall {
+            agent,
+            function,
+            prompt,
+            response,
+            estimated_tokens,
+            latency_ms,
+            attempts,
+        } => Some((
+            record,
+            agent,
+            function,
+            prompt,
+            response,
+            estimated_tokens,
+            latency_ms,
+            attempts,
+        )),
+        _ => None,
+    });
+    for (index, (record, agent, function, prompt, response, tokens, latency_ms, attempts)) in
+        llm_calls.enumerate()
+    {
+        writeln!(
+            out,
+            "#{} {:>8.1}s  {}: {} (~{} tokens, {} ms, {} attempt(s))",
+            index + 1,
+            elapsed(record),
+            agent,
+            function,
+            tokens,
+            latency_ms,
+            attempts
+        )
+        .unwrap();
+        for message in prompt {
+            writeln!(out, "  {}:", message.role).unwrap();
+            writeln!(out, "{}", indented(&preview(&message.content, is_full))).unwrap();
+        }
+        writeln!(out, "  response:").unwrap();
+        writeln!(out, "{}", indented(&preview(response, is_full))).unwrap();
+    }
+
+    writeln!(out, "\n== Backend code").unwrap();
+    let code_versions: Vec<&str> = run
+        .events
+        .iter()
+        .filter_map(|record| match &record.event {
+            RunEvent::BackendCode { code, .. } => Some(code.as_str()),
+            _ => None,
+        })
+        .collect();
+    for (index, code) in code_versions.iter().enumerate() {
+        if index == 0 {
+            writeln!(out, "version 1: {} lines", code.lines().count()).unwrap();
+            continue;
+        }
+        let diff = TextDiff::from_lines(code_versions[index - 1], *code)
+            .unified_diff()
+            .context_radius(3)
+            .header(
+                &format!("version {}", index),
+                &format!("version {}", index + 1),
+            )
+            .to_string();
+        write!(out, "{}", diff).unwrap();
+    }
+
+    writeln!(out, "\n== Builds").unwrap();
+    let builds = run.events.iter().filter_map(|record| match &record.event {
+        RunEvent::Build {
+            is_success, errors, ..
+        } => Some((is_success, errors)),
+        _ => None,
+    });
+    for (round, (is_success, errors)) in builds.enumerate() {
+        if *is_success {
+            writeln!(out, "round {}: ok", round + 1).unwrap();
+        } else {
+            writeln!(out, "round {}: failed", round + 1).unwrap();
+            writeln!(out, "{}", indented(&preview(errors, is_full))).unwrap();
+        }
+    }
+
+    writeln!(out, "\n== Endpoints").unwrap();
+    for record in &run.events {
+        if let RunEvent::EndpointTest {
+            route,
+            has_credentials,
+            expected_status,
+            status,
+            ..
+        } = &record.event
+        {
+            let result = if *status == Some(*expected_status) {
+                "ok  "
+            } else {
+                "FAIL"
+            };
+            let credentials = if *has_credentials { "with" } else { "without" };
+            let status = status.map_or("no answer".to_string(), |status| status.to_string());
+            writeln!(
+                out,
+                "{} {} {} credentials: {}, expected {}",
+                result, route, credentials, status, expected_status
+            )
+            .unwrap();
+        }
+    }
+
+    writeln!(out, "\n== FactSheet").unwrap();
+    match &run.factsheet {
+        Some(factsheet) => {
+            writeln!(out, "goal: {}", factsheet.project_description).unwrap();
+            writeln!(out, "scope: {:?}", factsheet.project_scope).unwrap();
+            writeln!(out, "external urls: {:?}", factsheet.external_urls).unwrap();
+            let endpoints = factsheet.api_endpoint_schema.as_ref().map_or(0, Vec::len);
+            writeln!(out, "tested endpoints: {}", endpoints).unwrap();
+        }
+        None => writeln!(out, "not written, the run did not finish").unwrap(),
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apis::recorded_responses::RecordedResponses;
+    use crate::helpers::events::EventLog;
+    use crate::helpers::events::EventSubscriber;
+    use crate::models::agent_basic::basic_agent::AgentState;
+    use crate::models::general::llm::Message;
+
+    const RUN_ID: u64 = 7;
+
+    fn record(timestamp_ms: u64, event: RunEvent) -> EventRecord {
+        EventRecord {
+            timestamp_ms,
+            run_id: Some(RUN_ID),
+            event,
+        }
+    }
+
+    fn llm_call(function: &str, response: &str) -> RunEvent {
+        RunEvent::LlmCall {
+            agent: "Backend Developer".to_string(),
+            function: function.to_string(),
+            prompt: vec![Message {
+                role: "system".to_string(),
+                content: "FUNCTION: prints code".to_string(),
+            }],
+            response: response.to_string(),
+            estimated_tokens: 10,
+            latency_ms: 900,
+            attempts: 1,
+        }
+    }
+
+    #[test]
+    fn test_inspect_a_recorded_run() {
+        let run_dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("inspect_{}", std::process::id()));
+        std::fs::remove_dir_all(&run_dir).ok();
+        let event_log = EventLog::create(&run_dir, RUN_ID).unwrap();
+        let backend = "Backend Developer".to_string();
+        let events = vec![
+            record(
+                1_000,
+                RunEvent::RunStarted {
+                    user_request: "a todo api".to_string(),
+                },
+            ),
+            record(
+                1_500,
+                RunEvent::StateChanged {
+                    agent: backend.clone(),
+                    state: AgentState::Working,
+                },
+            ),
+            record(
+                2_000,
+                llm_call("print_backend_webserver_code", "fn main() {\n}\n"),
+            ),
+            record(
+                2_000,
+                RunEvent::BackendCode {
+                    agent: backend.clone(),
+                    code: "fn main() {\n}\n".to_string(),
+                },
+            ),
+            record(
+                3_000,
+                RunEvent::Build {
+                    agent: backend.clone(),
+                    is_success: false,
+                    errors: "error[E0425]: cannot find value `x`".to_string(),
+                },
+            ),
+            record(
+                4_000,
+                llm_call("print_fixed_code", "fn main() {\n    run();\n}\n"),
+            ),
+            record(
+                4_000,
+                RunEvent::BackendCode {
+                    agent: backend.clone(),
+                    code: "fn main() {\n    run();\n}\n".to_string(),
+                },
+            ),
+            record(
+                5_000,
+                RunEvent::EndpointTest {
+                    agent: backend,
+                    route: "/todos".to_string(),
+                    has_credentials: false,
+                    expected_status: 200,
+                    status: Some(500),
+                },
+            ),
+        ];
+        for event in &events {
+            event_log.on_event(event);
+        }
+
+        let run = RunRecord::load(&run_dir).unwrap();
+        assert_eq!(run.events, events);
+        assert_eq!(run.user_request().unwrap(), "a todo api");
+        assert!(run.factsheet.is_none());
+
+        let inspection = render_inspection(&run, false);
+        assert!(inspection.contains("     0.5s  Backend Developer      Working"));
+        assert!(
+            inspection.contains("#2      3.0s  Backend Developer: print_fixed_code (~10 tokens")
+        );
+        assert!(inspection.contains("--- version 1\n+++ version 2\n"));
+        assert!(inspection.contains("+    run();"));
+        assert!(inspection.contains("round 1: failed\n    error[E0425]"));
+        assert!(inspection.contains("FAIL /todos without credentials: 500, expected 200"));
+
+        // Replay hands out the responses of each function of an agent in order
+        let recorded = RecordedResponses::from_events(&run.events);
+        assert_eq!(
+            recorded
+                .next("Backend Developer", "print_fixed_code")
+                .as_deref(),
+            Some("fn main() {\n    run();\n}\n")
+        );
+        assert_eq!(recorded.next("Backend Developer", "print_fixed_code"), None);
+        assert_eq!(
+            recorded.next("Backend Developer", "print_project_scope"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_skips_a_cut_off_last_line() {
+        let run_dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("inspect_cut_off_{}", std::process::id()));
+        std::fs::remove_dir_all(&run_dir).ok();
+        std::fs::create_dir_all(&run_dir).unwrap();
+        let started = record(
+            1_000,
+            RunEvent::RunStarted {
+                user_request: "a todo api".to_string(),
+            },
+        );
+        let line = serde_json::to_string(&started).unwrap();
+        let cut_off = &line[..line.len() / 2];
+        let write_log = |log: String| std::fs::write(run_dir.join(EVENT_LOG_FILE), log).unwrap();
+
+        write_log(format!("{}\n{}", line, cut_off));
+        let run = RunRecord::load(&run_dir).unwrap();
+        assert_eq!(run.events, vec![started.clone()]);
+
+        // Anywhere else a broken line is still an error
+        write_log(format!("{}\n{}\n", cut_off, line));
+        assert!(matches!(
+            RunRecord::load(&run_dir),
+            Err(InspectError::Parse { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_run_dir_args() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let inspect =
+            RunDirArgs::parse(INSPECT_SUBCOMMAND, &args(&["runs/run-1", "--full"])).unwrap();
+        assert_eq!(inspect.run_dir, PathBuf::from("runs/run-1"));
+        assert!(inspect.is_full);
+
+        let replay = RunDirArgs::parse(
+            REPLAY_SUBCOMMAND,
+            &args(&["runs/run-1", "--model", "gpt-4o"]),
+        )
+        .unwrap();
+        assert_eq!(replay.config_args, args(&["--model", "gpt-4o"]));
+        assert!(RunDirArgs::parse(INSPECT_SUBCOMMAND, &args(&["runs/run-1", "--model"])).is_err());
+        assert!(RunDirArgs::parse(REPLAY_SUBCOMMAND, &[]).is_err());
+    }
+}
@@ -0,0 +1,279 @@
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const KNOWLEDGE_BASE_FILE: &str = "knowledge.db";
+// Below this similarity a stored entry is not worth putting in a prompt
+const MIN_SIMILARITY: f64 = 0.2;
+// Upper bound of changed lines stored for one fix
+const MAX_FIX_LINES: usize = 30;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS validated_urls (
+    url TEXT PRIMARY KEY,
+    project_description TEXT NOT NULL,
+    validated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS code_fixes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    error TEXT NOT NULL UNIQUE,
+    fix TEXT NOT NULL,
+    recorded_at INTEGER NOT NULL
+);";
+
+type KnowledgeBaseError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct KnownFix {
+    pub error: String,
+    pub fix: String,
+}
+
+// What previous runs learnt: external urls which answered 200 and compile errors with their fix
+// A connection is opened per operation so agents holding the knowledge base stay Send + Sync
+#[derive(Debug, Clone)]
+pub struct KnowledgeBase {
+    path: PathBuf,
+}
+
+impl KnowledgeBase {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    // Stored under the cache dir, shared by every run
+    pub fn in_cache_dir(cache_dir: &Path) -> Self {
+        Self::new(&cache_dir.join(KNOWLEDGE_BASE_FILE))
+    }
+
+    fn connect(&self) -> Result<Connection, KnowledgeBaseError> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(&self.path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    }
+
+    pub fn record_validated_url(
+        &self,
+        url: &str,
+        project_description: &str,
+    ) -> Result<(), KnowledgeBaseError> {
+        self.connect()?.execute(
+            "INSERT INTO validated_urls (url, project_description, validated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(url) DO UPDATE SET
+                project_description = excluded.project_description,
+                validated_at = excluded.validated_at",
+            params![url, project_description, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    // A url is not trusted anymore once it stopped answering
+    pub fn forget_url(&self, url: &str) -> Result<(), KnowledgeBaseError> {
+        self.connect()?
+            .execute("DELETE FROM validated_urls WHERE url = ?1", params![url])?;
+        Ok(())
+    }
+
+    // Urls validated for the projects closest to this description, best match first
+    pub fn similar_urls(
+        &self,
+        project_description: &str,
+        limit: usize,
+    ) -> Result<Vec<String>, KnowledgeBaseError> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare("SELECT url, project_description FROM validated_urls")?;
+        let rows: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let ranked = rank_by_similarity(project_description, rows, |(_, description)| description);
+        Ok(ranked.into_iter().take(limit).map(|(url, _)| url).collect())
+    }
+
+    pub fn record_fix(&self, error: &str, fix: &str) -> Result<(), KnowledgeBaseError> {
+        self.connect()?.execute(
+            "INSERT INTO code_fixes (error, fix, recorded_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(error) DO UPDATE SET
+                fix = excluded.fix,
+                recorded_at = excluded.recorded_at",
+            params![error, fix, unix_now()],
+        )?;
+        Ok(())
+    }
+
+    // Fixes of the errors closest to this one, best match first
+    pub fn similar_fixes(
+        &self,
+        error: &str,
+        limit: usize,
+    ) -> Result<Vec<KnownFix>, KnowledgeBaseError> {
+        let conn = self.connect()?;
+        let mut stmt = conn.prepare("SELECT error, fix FROM code_fixes")?;
+        let rows: Vec<KnownFix> = stmt
+            .query_map([], |row| {
+                Ok(KnownFix {
+                    error: row.get(0)?,
+                    fix: row.get(1)?,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let ranked = rank_by_similarity(&summarize_compile_errors(error), rows, |known_fix| {
+            &known_fix.error
+        });
+        Ok(ranked.into_iter().take(limit).collect())
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.len() >= 3 && !word.chars().all(|c| c.is_ascii_digit()))
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+// Jaccard similarity of the keywords of both texts, between 0 and 1
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let keywords_a = keywords(a);
+    let keywords_b = keywords(b);
+    let union = keywords_a.union(&keywords_b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    keywords_a.intersection(&keywords_b).count() as f64 / union as f64
+}
+
+fn rank_by_similarity<T>(query: &str, rows: Vec<T>, text: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut scored: Vec<(f64, T)> = rows
+        .into_iter()
+        .map(|row| (similarity(query, text(&row)), row))
+        .filter(|(score, _)| *score >= MIN_SIMILARITY)
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    scored.into_iter().map(|(_, row)| row).collect()
+}
+
+// Keep the error lines of a cargo build output, without the compiling noise, paths and line numbers
+pub fn summarize_compile_errors(build_output: &str) -> String {
+    build_output
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            line.starts_with("error") || line.starts_with("= help") || line.starts_with("= note")
+        })
+        .filter(|line| !line.starts_with("error: could not compile"))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+// Lines the fix introduced, which is what a later run needs to see
+pub fn changed_lines(before: &str, after: &str) -> String {
+    let before_lines: HashSet<&str> = before.lines().map(str::trim).collect();
+    after
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !before_lines.contains(line))
+        .take(MAX_FIX_LINES)
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_knowledge_base(name: &str) -> KnowledgeBase {
+        let path = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("{}_{}.db", name, std::process::id()));
+        std::fs::remove_file(&path).ok();
+        KnowledgeBase::new(&path)
+    }
+
+    #[test]
+    fn test_similar_urls_ranks_closest_projects() {
+        let knowledge_base = test_knowledge_base("kb_urls");
+        knowledge_base
+            .record_validated_url(
+                "https://api.coinbase.com/v2/prices/spot",
+                "build a website that shows crypto prices",
+            )
+            .unwrap();
+        knowledge_base
+            .record_validated_url(
+                "https://api.open-meteo.com/v1/forecast",
+                "build a website that shows the weather forecast",
+            )
+            .unwrap();
+        knowledge_base
+            .record_validated_url("https://example.com/unrelated", "todo list with notes")
+            .unwrap();
+
+        let urls = knowledge_base
+            .similar_urls("build a website that shows bitcoin and crypto prices", 5)
+            .unwrap();
+        assert_eq!(urls[0], "https://api.coinbase.com/v2/prices/spot");
+        assert!(!urls.contains(&"https://example.com/unrelated".to_string()));
+
+        knowledge_base
+            .forget_url("https://api.coinbase.com/v2/prices/spot")
+            .unwrap();
+        let urls = knowledge_base
+            .similar_urls("build a website that shows bitcoin and crypto prices", 5)
+            .unwrap();
+        assert!(!urls.contains(&"https://api.coinbase.com/v2/prices/spot".to_string()));
+    }
+
+    #[test]
+    fn test_similar_fixes_matches_compile_errors() {
+        let knowledge_base = test_knowledge_base("kb_fixes");
+        let build_output = "   Compiling web_template v0.1.0
+error[E0433]: failed to resolve: use of undeclared type `HttpResponse`
+  --> src/main.rs:12:5
+   = help: consider importing this struct: use actix_web::HttpResponse;
+error: could not compile `web_template`";
+        let error = summarize_compile_errors(build_output);
+        assert_eq!(
+            error,
+            "error[E0433]: failed to resolve: use of undeclared type `HttpResponse`\n= help: consider importing this struct: use actix_web::HttpResponse;"
+        );
+        knowledge_base
+            .record_fix(&error, "use actix_web::HttpResponse;")
+            .unwrap();
+
+        let fixes = knowledge_base
+            .similar_fixes(
+                "error[E0433]: failed to resolve: use of undeclared type `HttpResponse`\n  --> src/main.rs:40:9",
+                3,
+            )
+            .unwrap();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].fix, "use actix_web::HttpResponse;");
+        assert!(knowledge_base
+            .similar_fixes("error: expected one of `,` or `}`", 3)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_changed_lines() {
+        let before = "use actix_web::web;\nfn main() {\n    run();\n}";
+        let after = "use actix_web::web;\nuse actix_web::HttpResponse;\nfn main() {\n    run();\n}";
+        assert_eq!(changed_lines(before, after), "use actix_web::HttpResponse;");
+    }
+}
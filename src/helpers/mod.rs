@@ -1,2 +1,10 @@
+pub mod benchmark;
 pub mod command_line;
+pub mod dashboard;
+pub mod eval;
+pub mod events;
 pub mod general;
+pub mod inspect;
+pub mod knowledge_base;
+pub mod prompts;
+pub mod telemetry;
@@ -0,0 +1,370 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+// A template of print_fixed_code is stored as print_fixed_code.txt
+const PROMPT_FILE_EXTENSION: &str = "txt";
+
+// Variables each ai_function is called with, the only ones its template may use.
+// Optional ones, e.g. KNOWN_WORKING_URLS, are left empty when the caller has none
+const PROMPT_VARIABLES: &[(&str, &[&str])] = &[
+    (
+        "convert_user_input_to_goal",
+        &["USER_REQUEST", "CLARIFICATIONS"],
+    ),
+    (
+        "print_clarifying_questions",
+        &["USER_REQUEST", "MAX_QUESTIONS"],
+    ),
+    ("print_project_scope", &["PROJECT_DESCRIPTION"]),
+    (
+        "print_site_urls",
+        &["PROJECT_DESCRIPTION", "KNOWN_WORKING_URLS"],
+    ),
+    ("print_database_entities", &["PROJECT_DESCRIPTION"]),
+    ("print_sqlite_migrations", &["DATABASE_ENTITIES"]),
+    (
+        "print_fixed_migrations",
+        &["BROKEN_MIGRATIONS", "DATABASE_ENTITIES", "ERROR_BUGS"],
+    ),
+    (
+        "print_persistence_module",
+        &["DATABASE_ENTITIES", "MIGRATIONS_PATH"],
+    ),
+    (
+        "print_backend_webserver_code",
+        &[
+            "CODE_TEMPLATE",
+            "PROJECT_DESCRIPTION",
+            "PROJECT_SCOPE",
+            "OPTIONAL_EXTERNAL_URLS",
+            "PERSISTENCE_MODULE",
+            "AUTH_MODULE",
+        ],
+    ),
+    (
+        "print_improved_webserver_code",
+        &["CODE_TEMPLATE", "PROJECT_DESCRIPTION"],
+    ),
+    (
+        "print_fixed_code",
+        &["BROKEN_CODE", "ERROR_BUGS", "KNOWN_FIXES"],
+    ),
+    ("print_rest_api_endpoints", &["CODE_INPUT"]),
+    ("print_security_review", &["CODE_INPUT", "STATIC_FINDINGS"]),
+    ("summarize_agent_memory", &["CONVERSATION"]),
+];
+
+#[derive(Debug)]
+pub enum PromptError {
+    Read {
+        path: PathBuf,
+        message: String,
+    },
+    UnknownFunction {
+        path: PathBuf,
+    },
+    UnknownVariable {
+        path: PathBuf,
+        name: String,
+        available: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for PromptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PromptError::Read { path, message } => {
+                write!(f, "cannot read {}: {}", path.display(), message)
+            }
+            PromptError::UnknownFunction { path } => {
+                write!(
+                    f,
+                    "{} is not the template of any ai_function",
+                    path.display()
+                )
+            }
+            PromptError::UnknownVariable {
+                path,
+                name,
+                available,
+            } => write!(
+                f,
+                "{} uses {{{{{}}}}}, expected one of {:?}",
+                path.display(),
+                name,
+                available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PromptError {}
+
+// Named values sent to an ai_function, e.g. PROJECT_DESCRIPTION or ERROR_BUGS
+// A prompt template refers to them as {{PROJECT_DESCRIPTION}}
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PromptInput {
+    vars: Vec<(String, String)>,
+}
+
+impl PromptInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.push(name, value);
+        self
+    }
+
+    pub fn push(&mut self, name: &str, value: impl Into<String>) {
+        self.vars.push((name.to_string(), value.into()));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars
+            .iter()
+            .find(|(var_name, _)| var_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // INPUT of the function printer, one "NAME: value" per variable
+    pub fn to_text(&self) -> String {
+        self.text_without(&HashSet::new())
+    }
+
+    fn text_without(&self, skipped: &HashSet<String>) -> String {
+        self.vars
+            .iter()
+            .filter(|(name, _)| !skipped.contains(name))
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<String>>()
+            .join(" \n ")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPrompt {
+    pub function: String,
+    pub input: String,
+}
+
+// Prompt templates read from disk at startup, the first dir holding a template wins
+// Without a template the ai_function doc comment is used, so an empty library changes nothing
+#[derive(Debug, Clone, Default)]
+pub struct PromptLibrary {
+    // ai_function name -> template
+    templates: HashMap<String, String>,
+}
+
+impl PromptLibrary {
+    // Every template is checked, shadowed ones too: a wrong variable fails the start, not a call
+    pub fn load(dirs: Vec<PathBuf>) -> Result<Self, PromptError> {
+        let mut templates = HashMap::new();
+        for dir in &dirs {
+            for (name, template) in read_templates(dir)? {
+                templates.entry(name).or_insert(template);
+            }
+        }
+        Ok(Self { templates })
+    }
+
+    // Variables used by a template are filled in and not repeated in the input
+    pub fn render(&self, name: &str, builtin: &str, input: &PromptInput) -> RenderedPrompt {
+        let Some(template) = self.templates.get(name) else {
+            return RenderedPrompt {
+                function: builtin.to_string(),
+                input: input.to_text(),
+            };
+        };
+        let mut used = HashSet::new();
+        let function = replace_variables(template, |name| {
+            used.insert(name.to_string());
+            input.get(name).unwrap_or_default().to_string()
+        });
+        RenderedPrompt {
+            function,
+            input: input.text_without(&used),
+        }
+    }
+}
+
+// Templates of a dir by ai_function name, a missing dir holds none
+fn read_templates(dir: &Path) -> Result<Vec<(String, String)>, PromptError> {
+    let read_error = |path: &Path, e: std::io::Error| PromptError::Read {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    };
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(read_error(dir, e)),
+    };
+
+    let mut templates = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| read_error(dir, e))?.path();
+        if !path.is_file()
+            || path.extension().and_then(|ext| ext.to_str()) != Some(PROMPT_FILE_EXTENSION)
+        {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let Some((_, variables)) = PROMPT_VARIABLES
+            .iter()
+            .find(|(function, _)| *function == name)
+        else {
+            return Err(PromptError::UnknownFunction { path });
+        };
+        let template = std::fs::read_to_string(&path).map_err(|e| read_error(&path, e))?;
+
+        let mut unknown_variable: Option<String> = None;
+        replace_variables(&template, |name| {
+            if !variables.contains(&name) && unknown_variable.is_none() {
+                unknown_variable = Some(name.to_string());
+            }
+            String::new()
+        });
+        if let Some(name) = unknown_variable {
+            return Err(PromptError::UnknownVariable {
+                path,
+                name,
+                available: variables.iter().map(|name| name.to_string()).collect(),
+            });
+        }
+        templates.push((name, template));
+    }
+    Ok(templates)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+// Replace every {{NAME}} with the value given for NAME
+fn replace_variables(template: &str, mut value: impl FnMut(&str) -> String) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        filled.push_str(&rest[..start]);
+        if is_variable_name(name) {
+            filled.push_str(&value(name));
+        } else {
+            // Not a variable, e.g. braces of a code example
+            filled.push_str(&rest[start..start + 4 + len]);
+        }
+        rest = &rest[start + 4 + len..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompts_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("{}_{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_render_falls_back_to_builtin() {
+        let library = PromptLibrary::load(vec![prompts_dir("prompts_empty")]).unwrap();
+        let input = PromptInput::new()
+            .with("BROKEN_CODE", "fn main() {")
+            .with("ERROR_BUGS", "unclosed delimiter");
+        let rendered = library.render("print_fixed_code", "FUNCTION: Removes bugs", &input);
+        assert_eq!(rendered.function, "FUNCTION: Removes bugs");
+        assert_eq!(
+            rendered.input,
+            "BROKEN_CODE: fn main() { \n ERROR_BUGS: unclosed delimiter"
+        );
+    }
+
+    #[test]
+    fn test_project_template_overrides_library() {
+        let project_dir = prompts_dir("prompts_project");
+        let library_dir = prompts_dir("prompts_library");
+        std::fs::write(
+            library_dir.join("print_fixed_code.txt"),
+            "Fix the code. {{ERROR_BUGS}}",
+        )
+        .unwrap();
+        std::fs::write(
+            library_dir.join("print_project_scope.txt"),
+            "Scope of {{PROJECT_DESCRIPTION}}, printed as {\"key\": bool} {{ json }}",
+        )
+        .unwrap();
+        std::fs::write(
+            project_dir.join("print_fixed_code.txt"),
+            "Errors: {{ERROR_BUGS}}",
+        )
+        .unwrap();
+        let library = PromptLibrary::load(vec![project_dir.clone(), library_dir.clone()]).unwrap();
+
+        let input = PromptInput::new()
+            .with("BROKEN_CODE", "fn main() {")
+            .with("ERROR_BUGS", "unclosed delimiter");
+        let fixed = library.render("print_fixed_code", "builtin", &input);
+        assert_eq!(fixed.function, "Errors: unclosed delimiter");
+        assert_eq!(fixed.input, "BROKEN_CODE: fn main() {");
+
+        let scope_input = PromptInput::new().with("PROJECT_DESCRIPTION", "a todo app");
+        let scope = library.render("print_project_scope", "builtin", &scope_input);
+        assert_eq!(
+            scope.function,
+            "Scope of a todo app, printed as {\"key\": bool} {{ json }}"
+        );
+        assert_eq!(scope.input, "");
+
+        // Optional variables the caller did not send are left empty
+        std::fs::write(
+            project_dir.join("print_site_urls.txt"),
+            "Urls for {{PROJECT_DESCRIPTION}}, known: {{KNOWN_WORKING_URLS}}.",
+        )
+        .unwrap();
+        let library = PromptLibrary::load(vec![project_dir, library_dir]).unwrap();
+        let urls = library.render("print_site_urls", "builtin", &scope_input);
+        assert_eq!(urls.function, "Urls for a todo app, known: .");
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_templates() {
+        let project_dir = prompts_dir("prompts_invalid_project");
+        let library_dir = prompts_dir("prompts_invalid_library");
+        std::fs::write(
+            project_dir.join("print_site_urls.txt"),
+            "{{PROJECT_DESCRIPTION}}",
+        )
+        .unwrap();
+        // Shadowed by the project template, still checked
+        std::fs::write(library_dir.join("print_site_urls.txt"), "{{CODE_TEMPLATE}}").unwrap();
+        let err = PromptLibrary::load(vec![project_dir.clone(), library_dir.clone()]).unwrap_err();
+        assert!(err.to_string().ends_with(
+            "uses {{CODE_TEMPLATE}}, expected one of [\"PROJECT_DESCRIPTION\", \"KNOWN_WORKING_URLS\"]"
+        ));
+
+        std::fs::remove_file(library_dir.join("print_site_urls.txt")).unwrap();
+        std::fs::write(project_dir.join("print_site_url.txt"), "Urls").unwrap();
+        let err = PromptLibrary::load(vec![project_dir, library_dir]).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("print_site_url.txt is not the template of any ai_function"));
+    }
+}
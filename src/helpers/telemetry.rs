@@ -0,0 +1,157 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use opentelemetry_proto::transform::common::tonic::ResourceAttributesWithSchema;
+use opentelemetry_proto::transform::trace::tonic::group_spans_by_resource_and_scope;
+use opentelemetry_sdk::error::{OTelSdkError, OTelSdkResult};
+use opentelemetry_sdk::trace::{SdkTracerProvider, SpanData, SpanExporter};
+use opentelemetry_sdk::Resource;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+// One OTLP JSON export request per line, in the run dir.
+// An OpenTelemetry collector reads it with the otlpjsonfile receiver, e.g. to show it in Jaeger
+pub const TRACE_FILE: &str = "traces.jsonl";
+const SERVICE_NAME: &str = "auto_gippity";
+// Spans written to the trace file when RUST_LOG is not set
+const DEFAULT_TRACE_FILTER: &str = "auto_gippity=info";
+
+#[derive(Debug)]
+struct OtlpFileExporter {
+    writer: Mutex<BufWriter<File>>,
+    resource: ResourceAttributesWithSchema,
+}
+
+impl OtlpFileExporter {
+    fn create(run_dir: &Path) -> std::io::Result<Self> {
+        std::fs::create_dir_all(run_dir)?;
+        let file = File::create(run_dir.join(TRACE_FILE))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+            resource: ResourceAttributesWithSchema::default(),
+        })
+    }
+}
+
+impl SpanExporter for OtlpFileExporter {
+    async fn export(&self, batch: Vec<SpanData>) -> OTelSdkResult {
+        let request = ExportTraceServiceRequest {
+            resource_spans: group_spans_by_resource_and_scope(batch, &self.resource),
+        };
+        let line = serde_json::to_string(&request)
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))?;
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(writer, "{}", line)
+            .and_then(|_| writer.flush())
+            .map_err(|e| OTelSdkError::InternalFailure(e.to_string()))
+    }
+
+    fn set_resource(&mut self, resource: &Resource) {
+        self.resource = resource.into();
+    }
+}
+
+fn tracer_provider(exporter: OtlpFileExporter) -> SdkTracerProvider {
+    SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build())
+        .with_batch_exporter(exporter)
+        .build()
+}
+
+// Keeps the trace file open until the run ends
+#[derive(Debug)]
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+
+// RUST_LOG filters the spans and events printed on stderr, nothing is printed without it.
+// With a trace dir, the spans also go to <trace dir>/traces.jsonl
+pub fn init(trace_dir: Option<&Path>) -> std::io::Result<Telemetry> {
+    let provider = trace_dir
+        .map(|trace_dir| OtlpFileExporter::create(trace_dir).map(tracer_provider))
+        .transpose()?;
+    let log_layer = std::env::var_os("RUST_LOG").map(|_| {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            // Each closed span is printed with its duration
+            .with_span_events(FmtSpan::CLOSE)
+            .with_filter(EnvFilter::from_default_env())
+    });
+    let trace_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer()
+            .with_tracer(provider.tracer(SERVICE_NAME))
+            .with_filter(
+                EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| EnvFilter::new(DEFAULT_TRACE_FILTER)),
+            )
+    });
+    tracing_subscriber::registry()
+        .with(log_layer)
+        .with(trace_layer)
+        .init();
+    Ok(Telemetry { provider })
+}
+
+impl Telemetry {
+    // Spans still in the batch are written before the file is closed
+    pub fn shutdown(self) {
+        if let Some(provider) = self.provider {
+            provider.shutdown().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans_are_written_as_otlp_json() {
+        let trace_dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("telemetry_{}", std::process::id()));
+        let provider = tracer_provider(OtlpFileExporter::create(&trace_dir).unwrap());
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME)));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("agent.execute", agent = "Backend Developer").in_scope(|| {
+                tracing::info_span!("llm.call", function = "print_fixed_code").in_scope(|| {});
+            });
+        });
+        provider.shutdown().unwrap();
+
+        let traces = std::fs::read_to_string(trace_dir.join(TRACE_FILE)).unwrap();
+        let requests: Vec<serde_json::Value> = traces
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let spans: Vec<&serde_json::Value> = requests
+            .iter()
+            .flat_map(|request| request["resourceSpans"].as_array().unwrap())
+            .flat_map(|resource_spans| resource_spans["scopeSpans"].as_array().unwrap())
+            .flat_map(|scope_spans| scope_spans["spans"].as_array().unwrap())
+            .collect();
+        assert_eq!(spans.len(), 2);
+        let span = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span["name"] == name)
+                .unwrap_or_else(|| panic!("no {} span in {}", name, traces))
+        };
+        assert_eq!(
+            span("llm.call")["parentSpanId"],
+            span("agent.execute")["spanId"]
+        );
+        assert_eq!(
+            span("llm.call")["traceId"],
+            span("agent.execute")["traceId"]
+        );
+        assert!(traces.contains(r#""stringValue":"auto_gippity""#));
+    }
+}
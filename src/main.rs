@@ -10,18 +10,157 @@ mod ai_functions;
 mod apis;
 mod helpers;
 mod models;
-use helpers::command_line::get_use_response;
+use apis::recorded_responses::RecordedResponses;
+use helpers::command_line::{
+    get_use_response, print_eval_summary, print_run_summary, PrintCommand, TerminalPrinter,
+};
+use helpers::dashboard;
+use helpers::eval::{run_eval, EvalArgs, EVAL_SUBCOMMAND};
+use helpers::events::{self, EventLog, EventSubscriber, RunEvent, RUN_FACTSHEET_FILE};
+use helpers::inspect::{
+    render_inspection, RunDirArgs, RunRecord, INSPECT_SUBCOMMAND, REPLAY_SUBCOMMAND,
+};
+use helpers::telemetry;
+use models::general::app_context::AppContext;
+use models::general::config::AppConfig;
+use std::env;
+use std::io::IsTerminal;
+use std::sync::Arc;
 
 use crate::models::agents_manager::managing_agent::ManagingAgent;
 #[tokio::main]
 async fn main() {
     // println!("Hello, world!");
-    let user_response = get_use_response("What kind of website do you want to create?");
-    let mut manager = ManagingAgent::new(user_response)
-        .await
-        .expect("Failed to create Managing Agent");
-    
-    manager.execute_project().await;
+    // auto_gippity.toml and the user config file, then the environment and the flags
+    // e.g. --no-cache: every LLM call goes to the API, nothing is read from or written to the cache
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Agent messages are events, printed by this subscriber unless the dashboard shows them
+    let terminal: Arc<dyn EventSubscriber> = Arc::new(TerminalPrinter);
+    events::subscribe(terminal.clone());
 
-    // dbg!(manager);
+    // eval: the benchmark suite, under two prompt versions with --candidate, see helpers::eval
+    if args.first().map(String::as_str) == Some(EVAL_SUBCOMMAND) {
+        let eval_args = EvalArgs::parse(&args[1..]).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        let _telemetry = telemetry::init(None).expect("Failed to set up tracing");
+        let eval_report = run_eval(&eval_args).await.expect("Failed to run eval");
+        print_eval_summary(&eval_report);
+        return;
+    }
+
+    // inspect: the timeline, LLM calls, code diffs and results of a recorded run, see helpers::inspect
+    if args.first().map(String::as_str) == Some(INSPECT_SUBCOMMAND) {
+        let inspect_args = RunDirArgs::parse(INSPECT_SUBCOMMAND, &args[1..]).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        let run = RunRecord::load(&inspect_args.run_dir).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        print!("{}", render_inspection(&run, inspect_args.is_full));
+        return;
+    }
+
+    // replay: a recorded run again, its LLM calls answered from the recording.
+    // The recorded code is built and run under the configured approval policy, --yes as usual
+    let (config_args, replay_run) = if args.first().map(String::as_str) == Some(REPLAY_SUBCOMMAND) {
+        let replay_args = RunDirArgs::parse(REPLAY_SUBCOMMAND, &args[1..]).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        let run = RunRecord::load(&replay_args.run_dir).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        });
+        (replay_args.config_args, Some(run))
+    } else {
+        (args, None)
+    };
+
+    let config = AppConfig::load(&config_args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let mut context = AppContext::new(config).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    });
+    let recorded_request = replay_run.map(|run| {
+        context.replay = Some(RecordedResponses::from_events(&run.events));
+        run.user_request()
+            .unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            })
+            .to_string()
+    });
+    let context = Arc::new(context);
+    let run_dir = context.config.new_run_dir();
+    // RUST_LOG prints spans and events on stderr, --traces also writes the spans to the run dir
+    let trace_dir = context
+        .config
+        .is_exporting_traces
+        .then_some(run_dir.as_path());
+    let telemetry = telemetry::init(trace_dir).unwrap_or_else(|e| {
+        eprintln!("No trace file in {}: {}", run_dir.display(), e);
+        telemetry::init(None).expect("Failed to set up tracing")
+    });
+    let event_log = match EventLog::create(&run_dir, context.run_id) {
+        Ok(event_log) => {
+            events::subscribe(Arc::new(event_log));
+            Some(run_dir)
+        }
+        Err(e) => {
+            eprintln!("No event log in {}: {}", run_dir.display(), e);
+            None
+        }
+    };
+
+    let user_response = recorded_request
+        .unwrap_or_else(|| get_use_response("What kind of website do you want to create?"));
+    // Everything the run emits from here goes to its event log
+    let (manager, project_res) = events::in_run(context.run_id, async {
+        events::emit(RunEvent::RunStarted {
+            user_request: user_response.clone(),
+        });
+        // Piped or redirected output stays plain
+        let dashboard =
+            (context.config.is_dashboard && std::io::stdout().is_terminal()).then(|| {
+                events::unsubscribe(&terminal);
+                dashboard::start(context.clone())
+            });
+        // No manager when the goal could not be set, nothing was built
+        let (manager, project_res) = match ManagingAgent::new(context.clone(), user_response).await
+        {
+            Ok(mut manager) => {
+                let project_res = manager.execute_project().await;
+                (Some(manager), project_res)
+            }
+            Err(e) => (None, Err(e)),
+        };
+        if let Some(dashboard) = dashboard {
+            dashboard.stop();
+            events::subscribe(terminal);
+        }
+        if let Err(e) = &project_res {
+            PrintCommand::Issue
+                .print_agent_message("Project Manager", &format!("Project failed: {}", e));
+        }
+        (manager, project_res)
+    })
+    .await;
+    if let (Some(run_dir), Some(manager)) = (&event_log, &manager) {
+        let factsheet_json =
+            serde_json::to_string_pretty(manager.factsheet()).expect("Failed to encode FactSheet");
+        std::fs::write(run_dir.join(RUN_FACTSHEET_FILE), factsheet_json)
+            .expect("Failed to write the FactSheet of the run");
+    }
+    print_run_summary(&context, event_log.as_deref());
+    telemetry.shutdown();
+    if project_res.is_err() {
+        std::process::exit(1);
+    }
 }
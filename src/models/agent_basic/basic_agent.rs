@@ -1,7 +1,15 @@
+use crate::helpers::events::{self, RunEvent};
 use crate::models::agent_basic::basic_trait::BasicTraits;
 use crate::models::general::llm::Message;
+use serde::{Deserialize, Serialize};
+use tracing::Span;
 
-#[derive(Debug, PartialEq)]
+// Above this estimate of the older turns, they are summarized
+pub const MEMORY_TOKEN_THRESHOLD: usize = 6000;
+// Latest request/response pairs which are always kept word for word, the current one included
+const RECENT_TURNS_KEPT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AgentState {
     Discovery,
     Working,
@@ -15,6 +23,75 @@ pub struct BasicAgent {
     pub position: String,
     pub state: AgentState,
     pub memory: Vec<Message>,
+    // Open while the agent is in its current state, the LLM calls of the agent are recorded under it
+    pub state_span: Span,
+    // Set once the agent is retried, see reset
+    pub is_cache_bypassed: bool,
+}
+
+impl BasicAgent {
+    // Back to Discovery for a retry or a re-run by the manager
+    pub fn reset(&mut self) {
+        self.update_state(AgentState::Discovery);
+        // The cached responses are the ones which failed, the LLM is asked again
+        self.is_cache_bypassed = true;
+        // Turns of the failed run, the summary included, must not steer the new one
+        self.memory.clear();
+    }
+
+    // Called at the start of each step of execute, so the span is a child of the agent span
+    pub fn open_state_span(&mut self) {
+        if self.state_span.is_none() && self.state != AgentState::Finished {
+            self.state_span = tracing::info_span!(
+                "agent.state",
+                agent = %self.position,
+                state = ?self.state
+            );
+        }
+    }
+
+    // Store a request and the response of the LLM as the latest turn
+    pub fn remember(&mut self, request: Message, response: &str) {
+        self.memory.push(request);
+        self.memory.push(Message {
+            role: "assistant".to_string(),
+            content: response.to_string(),
+        });
+    }
+
+    // Turns to summarize once they are too large. The latest turns are neither summarized
+    // nor counted: the current prompt alone, e.g. a large code template, triggers nothing
+    pub fn memory_to_summarize(&self) -> Option<&[Message]> {
+        let kept = RECENT_TURNS_KEPT * 2;
+        if self.memory.len() <= kept {
+            return None;
+        }
+        let older_turns = &self.memory[..self.memory.len() - kept];
+        if estimate_tokens(older_turns) <= MEMORY_TOKEN_THRESHOLD {
+            return None;
+        }
+        Some(older_turns)
+    }
+
+    // Replace the summarized turns with a single message
+    pub fn compress_memory(&mut self, summary: &str) {
+        let kept = (RECENT_TURNS_KEPT * 2).min(self.memory.len());
+        let recent = self.memory.split_off(self.memory.len() - kept);
+        self.memory = vec![Message {
+            role: "system".to_string(),
+            content: format!("SUMMARY OF EARLIER TURNS: {}", summary),
+        }];
+        self.memory.extend(recent);
+    }
+}
+
+// Rough estimate, about 4 characters per token
+fn estimate_tokens(messages: &[Message]) -> usize {
+    messages
+        .iter()
+        .map(|message| message.content.len())
+        .sum::<usize>()
+        / 4
 }
 
 impl BasicTraits for BasicAgent {
@@ -24,11 +101,19 @@ impl BasicTraits for BasicAgent {
             position,
             state: AgentState::Discovery,
             memory: Vec::new(),
+            state_span: Span::none(),
+            is_cache_bypassed: false,
         }
     }
 
     fn update_state(&mut self, new_state: AgentState) {
+        events::emit(RunEvent::StateChanged {
+            agent: self.position.clone(),
+            state: new_state,
+        });
         self.state = new_state;
+        // The span of the previous state ends here, see open_state_span
+        self.state_span = Span::none();
     }
 
     fn get_objective(&self) -> &String {
@@ -38,12 +123,57 @@ impl BasicTraits for BasicAgent {
     fn get_position(&self) -> &String {
         &self.position
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn get_state(&self) -> &AgentState {
-        &self.state
+    fn message(content: &str) -> Message {
+        Message {
+            role: "system".to_string(),
+            content: content.to_string(),
+        }
     }
 
-    fn get_memory(&self) -> &Vec<Message> {
-        &self.memory
+    #[test]
+    fn test_memory_is_summarized_past_threshold() {
+        let mut agent = BasicAgent::new("objective".to_string(), "Tester".to_string());
+        agent.remember(message("first request"), "first response");
+        assert_eq!(agent.memory.len(), 2);
+        assert_eq!(agent.memory[1].role, "assistant");
+        assert!(agent.memory_to_summarize().is_none());
+
+        // A large prompt is not summarized while it is one of the latest turns
+        let large_request = "x".repeat(MEMORY_TOKEN_THRESHOLD * 4);
+        agent.remember(message(&large_request), "second response");
+        agent.remember(message("third request"), "third response");
+        assert!(agent.memory_to_summarize().is_none());
+
+        agent.remember(message("fourth request"), "fourth response");
+        assert_eq!(
+            agent.memory_to_summarize().map(|turns| turns.len()),
+            Some(4)
+        );
+
+        agent.compress_memory("earlier attempts failed to compile");
+        assert_eq!(agent.memory.len(), 5);
+        assert!(agent.memory[0].content.contains("earlier attempts failed"));
+        assert_eq!(agent.memory[1].content, "third request");
+        assert_eq!(agent.memory[3].content, "fourth request");
+        assert!(agent.memory_to_summarize().is_none());
+    }
+
+    #[test]
+    fn test_reset_forgets_the_failed_run() {
+        let mut agent = BasicAgent::new("objective".to_string(), "Tester".to_string());
+        agent.remember(message("request"), "response");
+        agent.compress_memory("the code did not compile");
+        agent.update_state(AgentState::UnitTesting);
+
+        agent.reset();
+        assert!(agent.memory.is_empty());
+        assert_eq!(agent.state, AgentState::Discovery);
+        assert!(agent.is_cache_bypassed);
     }
 }
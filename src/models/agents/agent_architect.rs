@@ -1,64 +1,98 @@
 use crate::ai_functions::aifunc_architect::{print_project_scope, print_site_urls};
 use crate::helpers::command_line::PrintCommand;
-use crate::helpers::general::{ai_task_request_decoded, check_status_code};
+use crate::helpers::general::{
+    agent_task_request_decoded, check_status_code, run_bounded, MAX_CONCURRENT_REQUESTS,
+};
+use crate::helpers::knowledge_base::KnowledgeBase;
+use crate::helpers::prompts::PromptInput;
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
 use crate::models::agent_basic::basic_trait::BasicTraits;
-use crate::models::agents::agent_traits::{FactSheet, ProjectScope, SpecialFunctions};
+use crate::models::agents::agent_traits::{
+    AgentError, FactSheet, FactSheetField, ProjectScope, SpecialFunctions,
+};
+use crate::models::general::app_context::AppContext;
 
 use async_trait::async_trait;
-use reqwest::Client;
-use std::os::linux::raw::stat;
-use std::time::Duration;
+use std::sync::Arc;
+
+// Urls validated in earlier runs suggested to the model
+const KNOWN_URLS_IN_PROMPT: usize = 5;
 
 // Solution Architect
 #[derive(Debug)]
 pub struct AgentSolutionArchitect {
     attributes: BasicAgent,
+    context: Arc<AppContext>,
+    knowledge_base: KnowledgeBase,
 }
 
 impl AgentSolutionArchitect {
-    pub fn new() -> Self {
-        let attributes = BasicAgent {
-            objective: "Gather information and design solutions for website development"
-                .to_string(),
-            position: "Solutions Architect".to_string(),
-            state: AgentState::Discovery,
-            memory: Vec::new(),
-        };
-        Self { attributes }
+    pub fn new(context: Arc<AppContext>) -> Self {
+        let attributes = BasicAgent::new(
+            "Gather information and design solutions for website development".to_string(),
+            "Solutions Architect".to_string(),
+        );
+        Self {
+            attributes,
+            knowledge_base: KnowledgeBase::in_cache_dir(&context.config.cache_dir),
+            context,
+        }
     }
 
-    async fn call_project_scope(&mut self, factsheet: &mut FactSheet) -> ProjectScope {
-        let msg_context = format!("{}", factsheet.project_description);
-        let ai_response: ProjectScope = ai_task_request_decoded(
-            msg_context,
-            &self.attributes.position,
+    async fn call_project_scope(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<ProjectScope, AgentError> {
+        // Confirmed by the user during discovery, see ManagingAgent
+        if let Some(project_scope) = &factsheet.project_scope {
+            self.attributes.update_state(AgentState::Finished);
+            return Ok(project_scope.clone());
+        }
+        let input = PromptInput::new().with(
+            "PROJECT_DESCRIPTION",
+            factsheet.project_description.as_str(),
+        );
+        let ai_response: ProjectScope = agent_task_request_decoded(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_project_scope),
             print_project_scope,
+            false,
         )
-        .await;
+        .await?;
 
         factsheet.project_scope = Some(ai_response.clone());
-        self.attributes.state = AgentState::Finished;
+        self.attributes.update_state(AgentState::Finished);
 
-        return ai_response;
+        Ok(ai_response)
     }
 
     async fn call_determine_external_urls(
         &mut self,
         factsheet: &mut FactSheet,
-        msg_context: String,
-    ) {
-        let ai_response: Vec<String> = ai_task_request_decoded(
-            msg_context,
-            &self.attributes.position,
+        input: PromptInput,
+    ) -> Result<(), AgentError> {
+        let ai_response: Vec<String> = agent_task_request_decoded(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_site_urls),
             print_site_urls,
+            false,
         )
-        .await;
+        .await?;
 
         factsheet.external_urls = Some(ai_response);
-        self.attributes.state = AgentState::UnitTesting;
+        self.attributes.update_state(AgentState::UnitTesting);
+        Ok(())
+    }
+
+    fn report_knowledge_base_error(&self, e: Box<dyn std::error::Error + Send + Sync>) {
+        PrintCommand::Issue.print_agent_message(
+            self.attributes.position.as_str(),
+            &format!("Knowledge base unavailable: {}", e),
+        );
     }
 }
 
@@ -68,21 +102,48 @@ impl SpecialFunctions for AgentSolutionArchitect {
         &self.attributes
     }
 
+    fn required_fields(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::ProjectDescription]
+    }
+
+    fn produced_fields(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::ProjectScope, FactSheetField::ExternalUrls]
+    }
+
+    fn reset(&mut self) {
+        self.attributes.reset();
+    }
+
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // !! WARNING: infinite loop -> infinite cost !!!!
         while self.attributes.state != AgentState::Finished {
+            self.attributes.open_state_span();
             match self.attributes.state {
                 AgentState::Discovery => {
-                    let project_scope = self.call_project_scope(factsheet).await;
+                    let project_scope = self.call_project_scope(factsheet).await?;
 
                     if project_scope.is_external_urls_required {
-                        let msg_context = format!("{}", factsheet.project_description);
-                        self.call_determine_external_urls(factsheet, msg_context)
-                            .await;
-                        self.attributes.state = AgentState::UnitTesting;
+                        let mut input = PromptInput::new().with(
+                            "PROJECT_DESCRIPTION",
+                            factsheet.project_description.as_str(),
+                        );
+                        let known_urls = self
+                            .knowledge_base
+                            .similar_urls(&factsheet.project_description, KNOWN_URLS_IN_PROMPT)
+                            .unwrap_or_else(|e| {
+                                self.report_knowledge_base_error(e);
+                                Vec::new()
+                            });
+                        if !known_urls.is_empty() {
+                            input.push("KNOWN_WORKING_URLS", format!("{:?}", known_urls));
+                        }
+                        self.call_determine_external_urls(factsheet, input).await?;
+                        self.attributes.update_state(AgentState::UnitTesting);
+                    } else {
+                        factsheet.external_urls = Some(Vec::new());
                     }
                 }
 
@@ -90,11 +151,6 @@ impl SpecialFunctions for AgentSolutionArchitect {
                 AgentState::UnitTesting => {
                     let mut exclude_urls: Vec<String> = Vec::new();
 
-                    let client = Client::builder()
-                        .timeout(Duration::from_secs(5))
-                        .build()
-                        .unwrap();
-
                     let urls = factsheet
                         .external_urls
                         .as_ref()
@@ -106,38 +162,63 @@ impl SpecialFunctions for AgentSolutionArchitect {
                             self.attributes.position.as_str(),
                             endpoint_str.as_str(),
                         );
+                    }
+
+                    // Urls are probed in parallel, a few at a time
+                    let url_checks: Vec<_> = urls
+                        .iter()
+                        .map(|url| check_status_code(&self.context.http, url))
+                        .collect();
+                    let status_codes = run_bounded(url_checks, MAX_CONCURRENT_REQUESTS).await;
 
-                        match check_status_code(&client, url).await {
+                    for (url, status_code_res) in urls.iter().zip(status_codes) {
+                        match status_code_res {
                             Ok(status_code) => {
                                 if status_code != 200 {
                                     exclude_urls.push(url.clone());
                                 }
                             }
                             Err(e) => {
-                                println!("Error checking {}: {}", url, e.to_string());
+                                PrintCommand::Issue.print_agent_message(
+                                    self.attributes.position.as_str(),
+                                    &format!("Error checking {}: {}", url, e),
+                                );
                                 exclude_urls.push(url.clone());
                             }
                         }
                     }
 
-                    if exclude_urls.len() > 0 {
-                        let mut new_urls: Vec<String> = factsheet
+                    // Next runs get the urls which answered, and stop suggesting the others
+                    for url in urls {
+                        let knowledge_res = if exclude_urls.contains(url) {
+                            self.knowledge_base.forget_url(url)
+                        } else {
+                            self.knowledge_base
+                                .record_validated_url(url, &factsheet.project_description)
+                        };
+                        if let Err(e) = knowledge_res {
+                            self.report_knowledge_base_error(e);
+                        }
+                    }
+
+                    if !exclude_urls.is_empty() {
+                        let new_urls: Vec<String> = factsheet
                             .external_urls
                             .as_ref()
                             .unwrap()
                             .iter()
-                            .filter(|url| !exclude_urls.contains(&url))
+                            .filter(|url| !exclude_urls.contains(url))
                             .cloned()
                             .collect();
                         factsheet.external_urls = Some(new_urls);
                     }
 
-                    self.attributes.state = AgentState::Finished;
+                    self.attributes.update_state(AgentState::Finished);
                 }
 
                 // Default to finished
                 _ => {
-                    self.attributes.state = AgentState::Finished;
+                    self.attributes.update_state(AgentState::Finished);
                 }
             }
         }
@@ -148,24 +229,30 @@ impl SpecialFunctions for AgentSolutionArchitect {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::general::config::AppConfig;
     #[tokio::test]
     async fn test_solution_architect() {
-        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new();
+        let context = AppContext::new(AppConfig::load(&[]).expect("Invalid configuration"))
+            .expect("Failed to create context");
+        let mut agent: AgentSolutionArchitect = AgentSolutionArchitect::new(Arc::new(context));
 
         let mut factsheet: FactSheet = FactSheet {
             // project_description: "build a full stack website with user login and logout that shows lastest Forex prices".to_string(),
             project_description: "build a full stack website with user login and logout that shows lastest US stock prices".to_string(),
+            clarifications: Vec::new(),
             project_scope: None,
             external_urls: None,
+            database_schema: None,
             backend_code: None,
-            api_endpoint_schema: None
+            api_endpoint_schema: None,
+            security_report: None,
         };
 
         agent
             .execute(&mut factsheet)
             .await
             .expect("Unable to execute Solution Architect");
-        assert!(factsheet.project_scope != None);
+        assert!(factsheet.project_scope.is_some());
         assert!(factsheet.external_urls.is_some());
 
         dbg!(factsheet);
@@ -4,46 +4,74 @@ use crate::ai_functions::aifunc_backend::{
 };
 
 use crate::helpers::general::{
-    check_status_code, read_code_template_content, read_exc_main_content, save_api_endpoints,
-    save_backend_code, WEB_SERVER_PROJECT_PATH,
+    check_status_code_with_token, ensure_web_server_dependency, login_test_user,
+    read_code_template_content, read_exc_main_content, run_bounded, save_api_endpoints,
+    save_auth_module, save_backend_code, AUTH_DEPENDENCIES, AUTH_MODULE, MAX_CONCURRENT_REQUESTS,
+    WEB_SERVER_PROJECT_PATH,
 };
 
 use crate::helpers::command_line::{confirm_safe_code, PrintCommand};
-use crate::helpers::general::ai_task_request;
+use crate::helpers::events::{self, RunEvent};
+use crate::helpers::general::{agent_task_request, agent_task_request_decoded};
+use crate::helpers::knowledge_base::{changed_lines, summarize_compile_errors, KnowledgeBase};
+use crate::helpers::prompts::PromptInput;
 use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
-use crate::models::agents::agent_traits::{FactSheet, RouteObject, SpecialFunctions};
+use crate::models::agent_basic::basic_trait::BasicTraits;
+use crate::models::agents::agent_security::{ApprovalDecision, ApprovalPolicy, SecurityReviewer};
+use crate::models::agents::agent_traits::{
+    AgentError, FactSheet, FactSheetField, RouteObject, SpecialFunctions,
+};
+use crate::models::general::app_context::AppContext;
 
 use async_trait::async_trait;
-use reqwest::Client;
-use std::fs;
-use std::os::linux::raw::stat;
 use std::process::{Command, Stdio};
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 
+const LOCAL_SERVER_URL: &str = "http://localhost:8080";
+// Fixes of earlier runs shown to the model when it fixes a build error
+const KNOWN_FIXES_IN_PROMPT: usize = 3;
+
 #[derive(Debug)]
 pub struct AgentBackendDev {
     attributes: BasicAgent,
+    context: Arc<AppContext>,
     bug_errors: Option<String>,
     bug_count: u8,
+    security_reviewer: SecurityReviewer,
+    approval_policy: ApprovalPolicy,
+    knowledge_base: KnowledgeBase,
+    // Compile errors and the code they were found in, recorded once a fix builds
+    pending_fix: Option<(String, String)>,
 }
 
 impl AgentBackendDev {
-    pub fn new() -> Self {
-        let attributes = BasicAgent {
-            objective: "Develop backend for the website".to_string(),
-            position: "Backend Developer".to_string(),
-            state: AgentState::Discovery,
-            memory: Vec::new(),
-        };
+    pub fn new(context: Arc<AppContext>) -> Self {
+        let attributes = BasicAgent::new(
+            "Develop backend for the website".to_string(),
+            "Backend Developer".to_string(),
+        );
         Self {
             attributes,
             bug_errors: None,
             bug_count: 0,
+            security_reviewer: SecurityReviewer::new(context.clone()),
+            approval_policy: if context.config.is_unattended {
+                context.config.approval_policy.unattended()
+            } else {
+                context.config.approval_policy
+            },
+            knowledge_base: KnowledgeBase::in_cache_dir(&context.config.cache_dir),
+            pending_fix: None,
+            context,
         }
     }
 
-    async fn call_initial_backend_code(&mut self, factsheet: &mut FactSheet) {
+    async fn call_initial_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
         // First version: junior dev
 
         let code_template_str = read_code_template_content();
@@ -55,116 +83,301 @@ impl AgentBackendDev {
             .map(|url| format!("\"{}\"", url))
             .collect::<Vec<String>>()
             .join(", ");
-        let project_desc = format!(
-            "CODE_TEMPLATE: {} \n PROJECT_DESCRIPTION: {} \n OPTIONAL_EXTERNAL_URLS: {}",
-            code_template_str, factsheet.project_description, external_urls
-        );
-        
-        let backend_code: String = ai_task_request(
-            project_desc,
-            &self.attributes.position,
+        let project_scope: String = serde_json::to_string(&factsheet.project_scope)
+            .expect("Failed to encode project scope");
+        let mut input = PromptInput::new()
+            .with("CODE_TEMPLATE", code_template_str)
+            .with(
+                "PROJECT_DESCRIPTION",
+                factsheet.project_description.as_str(),
+            )
+            .with("PROJECT_SCOPE", project_scope)
+            .with("OPTIONAL_EXTERNAL_URLS", external_urls);
+
+        // CRUD projects must store their data through the module written by the Database Engineer
+        if let Some(persistence_module) = factsheet
+            .database_schema
+            .as_ref()
+            .and_then(|schema| schema.persistence_module.as_ref())
+        {
+            input.push("PERSISTENCE_MODULE", persistence_module.as_str());
+        }
+
+        // Login and logout are never written by the model: the vetted auth module is injected instead
+        if is_user_login_required(factsheet) {
+            save_auth_module();
+            for dependency_line in AUTH_DEPENDENCIES {
+                ensure_web_server_dependency(dependency_line);
+            }
+            input.push("AUTH_MODULE", AUTH_MODULE);
+        }
+
+        let backend_code: String = agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_backend_webserver_code),
             print_backend_webserver_code,
+            false,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&backend_code);
-        factsheet.backend_code = Some(backend_code);
+        self.store_backend_code(factsheet, backend_code);
+        Ok(())
     }
 
-    async fn call_improved_backend_code(&mut self, factsheet: &mut FactSheet) {
+    async fn call_improved_backend_code(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
         // Here comes the senior dev
 
-        let msg_context = format!(
-            "CODE_TEMPLATE: {:?} \n PROJECT_DESCRIPTION: {:?}\n
-            THIS FUNCTION ONLY PRINTS THE FIXED CODE. NOTHING ELSE. NO COMMENTARY.",
-            factsheet.backend_code, factsheet
-        );
-        let backend_code: String = ai_task_request(
-            msg_context,
-            &self.attributes.position,
+        let input = PromptInput::new()
+            .with("CODE_TEMPLATE", format!("{:?}", factsheet.backend_code))
+            .with("PROJECT_DESCRIPTION", format!("{:?}", factsheet));
+        let backend_code: String = agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_improved_webserver_code),
             print_improved_webserver_code,
+            false,
         )
-        .await;
+        .await?;
 
-        save_backend_code(&backend_code);
-        factsheet.backend_code = Some(backend_code);
+        self.store_backend_code(factsheet, backend_code);
+        Ok(())
     }
 
-    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) {
-        let msg_context = format!(
-            "BROKEN_CODE: {:?} \n ERROR_BUGS: {:?}\n
-            THIS FUNCTION ONLY PRINTS THE FIXED CODE. NOTHING ELSE. NO COMMENTARY.",
-            factsheet.backend_code, self.bug_errors
-        );
-        let backend_code: String = ai_task_request(
-            msg_context,
-            &self.attributes.position,
+    async fn call_fix_code_bugs(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let error_str = self.bug_errors.clone().unwrap_or_default();
+        let known_fixes = self
+            .knowledge_base
+            .similar_fixes(&error_str, KNOWN_FIXES_IN_PROMPT)
+            .unwrap_or_else(|e| {
+                PrintCommand::Issue.print_agent_message(
+                    self.attributes.position.as_str(),
+                    &format!("Knowledge base unavailable: {}", e),
+                );
+                Vec::new()
+            });
+
+        self.context.metrics.record_fix_round();
+        let mut input = PromptInput::new()
+            .with("BROKEN_CODE", format!("{:?}", factsheet.backend_code))
+            .with("ERROR_BUGS", format!("{:?}", self.bug_errors));
+        if !known_fixes.is_empty() {
+            input.push(
+                "KNOWN_FIXES",
+                serde_json::to_string(&known_fixes).expect("Failed to encode known fixes"),
+            );
+        }
+        self.pending_fix = Some((
+            summarize_compile_errors(&error_str),
+            factsheet.backend_code.clone().unwrap_or_default(),
+        ));
+
+        let backend_code: String = agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_fixed_code),
             print_fixed_code,
+            true,
         )
-        .await;
+        .await?;
 
+        self.store_backend_code(factsheet, backend_code);
+        Ok(())
+    }
+
+    // Every version of the code is in the event log, to compare them after the run
+    fn store_backend_code(&self, factsheet: &mut FactSheet, backend_code: String) {
         save_backend_code(&backend_code);
+        events::emit(RunEvent::BackendCode {
+            agent: self.attributes.position.clone(),
+            code: backend_code.clone(),
+        });
         factsheet.backend_code = Some(backend_code);
     }
 
-    async fn call_extract_rest_api_schema(&mut self) -> String {
-        let msg_context = format!("CODE_INPUT: {}", read_exc_main_content());
-        let api_schema: String = ai_task_request(
-            msg_context,
-            &self.attributes.position,
+    // The last fix builds: keep what changed for the next runs meeting the same errors
+    fn record_successful_fix(&mut self, factsheet: &FactSheet) {
+        let Some((error, broken_code)) = self.pending_fix.take() else {
+            return;
+        };
+        let fix = changed_lines(
+            &broken_code,
+            factsheet.backend_code.as_deref().unwrap_or_default(),
+        );
+        if error.is_empty() || fix.is_empty() {
+            return;
+        }
+        if let Err(e) = self.knowledge_base.record_fix(&error, &fix) {
+            PrintCommand::Issue.print_agent_message(
+                self.attributes.position.as_str(),
+                &format!("Knowledge base unavailable: {}", e),
+            );
+        }
+    }
+
+    async fn call_extract_rest_api_schema(&mut self) -> Result<Vec<RouteObject>, AgentError> {
+        let input = PromptInput::new().with("CODE_INPUT", read_exc_main_content());
+        let api_schema: Vec<RouteObject> = agent_task_request_decoded(
+            &self.context,
+            &mut self.attributes,
+            input,
             get_function_string!(print_rest_api_endpoints),
             print_rest_api_endpoints,
+            false,
         )
-        .await;
-        api_schema
+        .await?;
+        Ok(api_schema)
     }
 }
 
+fn is_user_login_required(factsheet: &FactSheet) -> bool {
+    factsheet
+        .project_scope
+        .as_ref()
+        .map(|scope| scope.is_user_login_and_logout)
+        .unwrap_or(false)
+}
+
+fn has_no_usable_external_urls(factsheet: &FactSheet) -> bool {
+    let is_external_urls_required = factsheet
+        .project_scope
+        .as_ref()
+        .map(|scope| scope.is_external_urls_required)
+        .unwrap_or(false);
+    let urls_count = factsheet
+        .external_urls
+        .as_ref()
+        .map_or(0, |urls| urls.len());
+    is_external_urls_required && urls_count == 0
+}
+
 #[async_trait]
 impl SpecialFunctions for AgentBackendDev {
     fn get_attributes_from_agent(&self) -> &BasicAgent {
         &self.attributes
     }
 
+    fn required_fields(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ProjectScope,
+            FactSheetField::ExternalUrls,
+        ]
+    }
+
+    // Only CRUD projects get a database schema
+    fn optional_fields(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::DatabaseSchema]
+    }
+
+    fn produced_fields(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::BackendCode,
+            FactSheetField::ApiEndpointSchema,
+            // Written by the security review of its own code before it runs
+            FactSheetField::SecurityReport,
+        ]
+    }
+
+    fn reset(&mut self) {
+        self.attributes.reset();
+        self.bug_errors = None;
+        self.bug_count = 0;
+        self.pending_fix = None;
+    }
+
     // This function will allow agent to execute their logic
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>> {
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         while self.attributes.state != AgentState::Finished {
+            self.attributes.open_state_span();
             match self.attributes.state {
                 AgentState::Discovery => {
-                    self.call_initial_backend_code(factsheet).await;
-                    self.attributes.state = AgentState::Working;
+                    // The architect dropped every url which did not respond: nothing to build on
+                    if has_no_usable_external_urls(factsheet) {
+                        return Err(Box::new(AgentError::BadInput {
+                            field: FactSheetField::ExternalUrls,
+                            reason: "none of the external urls can be used".to_string(),
+                        }));
+                    }
+
+                    self.call_initial_backend_code(factsheet).await?;
+                    self.attributes.update_state(AgentState::Working);
                     continue;
                 }
                 AgentState::Working => {
                     if self.bug_count == 0 {
-                        self.call_improved_backend_code(factsheet).await;
+                        self.call_improved_backend_code(factsheet).await?;
                     } else {
-                        self.call_fix_code_bugs(factsheet).await;
+                        self.call_fix_code_bugs(factsheet).await?;
                     }
-                    self.attributes.state = AgentState::UnitTesting;
+                    self.attributes.update_state(AgentState::UnitTesting);
                     continue;
                 }
                 AgentState::UnitTesting => {
                     // Safe guard
                     PrintCommand::UnitTest.print_agent_message(
-                        &self.attributes.position.as_str(),
-                        "Backend Unit Testing: Need user input ...",
+                        self.attributes.position.as_str(),
+                        "Backend Unit Testing: reviewing the code before execution ...",
                     );
 
-                    let is_safe_code = confirm_safe_code();
-                    if !is_safe_code {
-                        panic!("Work on AI ethics !");
+                    let security_report = self
+                        .security_reviewer
+                        .review(&mut self.attributes, factsheet)
+                        .await?;
+                    match self.approval_policy.decide(&security_report) {
+                        ApprovalDecision::Approve => {
+                            PrintCommand::UnitTest.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Backend Unit Testing: security review is clean, auto-approved",
+                            );
+                        }
+                        ApprovalDecision::AskUser if self.context.config.is_unattended => {
+                            PrintCommand::UnitTest.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Backend Unit Testing: unattended run, executing without asking",
+                            );
+                            events::emit(RunEvent::UserApproval {
+                                agent: self.attributes.position.clone(),
+                                is_approved: true,
+                                is_unattended: true,
+                            });
+                        }
+                        ApprovalDecision::AskUser => {
+                            let is_safe_code = confirm_safe_code();
+                            events::emit(RunEvent::UserApproval {
+                                agent: self.attributes.position.clone(),
+                                is_approved: is_safe_code,
+                                is_unattended: false,
+                            });
+                            if !is_safe_code {
+                                return Err(Box::new(AgentError::Fatal(
+                                    "the user stopped the project".to_string(),
+                                )));
+                            }
+                        }
+                        ApprovalDecision::Block => {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Backend Unit Testing: execution blocked by the security review",
+                            );
+                            return Err(Box::new(AgentError::Fatal(
+                                "high severity security findings".to_string(),
+                            )));
+                        }
                     }
 
                     // Build and test code
                     PrintCommand::UnitTest.print_agent_message(
-                        &self.attributes.position.as_str(),
+                        self.attributes.position.as_str(),
                         "Backend Unit Testing: building the project ...",
                     );
 
@@ -176,10 +389,19 @@ impl SpecialFunctions for AgentBackendDev {
                         .output()
                         .expect("Failed to build backend application");
 
+                    self.context
+                        .metrics
+                        .record_build(build_backend_server.status.success());
+                    events::emit(RunEvent::Build {
+                        agent: self.attributes.position.clone(),
+                        is_success: build_backend_server.status.success(),
+                        errors: String::from_utf8_lossy(&build_backend_server.stderr).to_string(),
+                    });
                     if build_backend_server.status.success() {
                         self.bug_count = 0;
+                        self.record_successful_fix(factsheet);
                         PrintCommand::UnitTest.print_agent_message(
-                            &self.attributes.position.as_str(),
+                            self.attributes.position.as_str(),
                             "Backend Unit Testing: Backend server is built successfully",
                         );
                     } else {
@@ -190,24 +412,23 @@ impl SpecialFunctions for AgentBackendDev {
                         self.bug_errors = Some(error_str);
 
                         // Too many bug: wow, stop, I am not that rich !
-                        if self.bug_count > 2 {
+                        if self.bug_count > self.context.config.max_bug_fixes {
                             PrintCommand::Issue.print_agent_message(
-                                &self.attributes.position.as_str(),
+                                self.attributes.position.as_str(),
                                 "Backend Unit Testing: Exit, too many bugs - AI becomes too expensive !",
                             );
-                            panic!("Error: too many bugs!");
+                            return Err(Box::new(AgentError::Retryable(
+                                "too many bugs in the backend code".to_string(),
+                            )));
                         }
 
-                        self.attributes.state = AgentState::Working;
+                        self.attributes.update_state(AgentState::Working);
                         continue;
                     }
 
                     // Extract and Test Rest API Endpoints'
-                    let api_endpoints_str = self.call_extract_rest_api_schema().await;
-
                     let api_endpoints: Vec<RouteObject> =
-                        serde_json::from_str(api_endpoints_str.as_str())
-                            .expect("Failed to decode API Endpoints");
+                        self.call_extract_rest_api_schema().await?;
 
                     // Define endpoints to check
                     let check_endpoints: Vec<RouteObject> = api_endpoints
@@ -222,9 +443,28 @@ impl SpecialFunctions for AgentBackendDev {
                     // Store API Endpoints: why just get methods ?
                     factsheet.api_endpoint_schema = Some(check_endpoints.clone()); //why cloned ? just take it ?
 
+                    // Only static GET routes are called: the body or path a write or dynamic route
+                    // expects is unknown, so protected ones among them are never exercised
+                    let unchecked_protected_routes: Vec<String> = api_endpoints
+                        .iter()
+                        .filter(|route| {
+                            route.is_route_protected == "true" && !check_endpoints.contains(route)
+                        })
+                        .map(|route| format!("{} {}", route.method.to_uppercase(), route.route))
+                        .collect();
+                    if !unchecked_protected_routes.is_empty() {
+                        PrintCommand::Issue.print_agent_message(
+                            self.attributes.position.as_str(),
+                            &format!(
+                                "WARNING: Protected routes not checked (only static GET routes are): {}",
+                                unchecked_protected_routes.join(", ")
+                            ),
+                        );
+                    }
+
                     // Run backend application
                     PrintCommand::UnitTest.print_agent_message(
-                        &self.attributes.position.as_str(),
+                        self.attributes.position.as_str(),
                         "Backend Unit Testing: running the project ...",
                     );
 
@@ -237,37 +477,105 @@ impl SpecialFunctions for AgentBackendDev {
                         .expect("Failed to run backend application");
 
                     PrintCommand::UnitTest.print_agent_message(
-                        &self.attributes.position.as_str(),
-                        "Backend Unit Testing: launching the project in 5 seconds ...",
+                        self.attributes.position.as_str(),
+                        &format!(
+                            "Backend Unit Testing: launching the project in {} seconds ...",
+                            self.context.config.server_startup_delay.as_secs()
+                        ),
                     );
 
-                    let sleep_dur: Duration = Duration::from_secs(5);
+                    let sleep_dur: Duration = self.context.config.server_startup_delay;
                     time::sleep(sleep_dur).await;
 
-                    // Check status code
-                    for endpoint in check_endpoints {
+                    let client = &self.context.local_http;
+
+                    // Protected routes are called with the token of a freshly logged in test user
+                    let mut auth_token: Option<String> = None;
+                    if check_endpoints
+                        .iter()
+                        .any(|route| route.is_route_protected == "true")
+                    {
+                        match login_test_user(client, LOCAL_SERVER_URL).await {
+                            Ok(token) if !token.is_empty() => auth_token = Some(token),
+                            Ok(_) => PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "WARNING: Failed to log in a test user 'no token in the login response'",
+                            ),
+                            Err(e) => {
+                                let err_msg: String =
+                                    format!("WARNING: Failed to log in a test user '{}'", e);
+                                PrintCommand::Issue.print_agent_message(
+                                    self.attributes.position.as_str(),
+                                    err_msg.as_str(),
+                                );
+                            }
+                        }
+                    }
+
+                    // Endpoint checks: (route, token, expected status)
+                    let mut checks: Vec<(&str, Option<&str>, u16)> = Vec::new();
+                    for endpoint in &check_endpoints {
                         let testing_msg: String = format!("Testing endpoint: '{}'", endpoint.route);
                         PrintCommand::UnitTest.print_agent_message(
-                            &self.attributes.position.as_str(),
+                            self.attributes.position.as_str(),
                             testing_msg.as_str(),
                         );
+                        if endpoint.is_route_protected == "true" {
+                            checks.push((&endpoint.route, None, 401));
+                            match auth_token.as_deref() {
+                                Some(token) => checks.push((&endpoint.route, Some(token), 200)),
+                                // Inconclusive without a logged in user: neither passed nor failed
+                                None => PrintCommand::Issue.print_agent_message(
+                                    self.attributes.position.as_str(),
+                                    &format!(
+                                        "WARNING: Endpoint '{}' with credentials not checked, no test user is logged in",
+                                        endpoint.route
+                                    ),
+                                ),
+                            }
+                        } else {
+                            checks.push((&endpoint.route, None, 200));
+                        }
+                    }
 
-                        let client = Client::builder()
-                            .timeout(Duration::from_secs(5))
-                            .build()
-                            .unwrap();
-
-                        // Test url
-                        let url = format!("http://localhost:8080{}", endpoint.route);
-                        match check_status_code(&client, &url).await {
+                    // Check status code, a few endpoints at a time
+                    let urls: Vec<String> = checks
+                        .iter()
+                        .map(|(route, _, _)| format!("{}{}", LOCAL_SERVER_URL, route))
+                        .collect();
+                    let endpoint_checks: Vec<_> = urls
+                        .iter()
+                        .zip(&checks)
+                        .map(|(url, (_, token, _))| {
+                            check_status_code_with_token(client, url, *token)
+                        })
+                        .collect();
+                    let status_codes = run_bounded(endpoint_checks, MAX_CONCURRENT_REQUESTS).await;
+
+                    for ((route, token, expected_status), status_code_res) in
+                        checks.iter().zip(status_codes)
+                    {
+                        let credentials = if token.is_some() { "with" } else { "without" };
+                        let status = status_code_res.as_ref().ok().copied();
+                        self.context
+                            .metrics
+                            .record_endpoint_check(status == Some(*expected_status));
+                        events::emit(RunEvent::EndpointTest {
+                            agent: self.attributes.position.clone(),
+                            route: route.to_string(),
+                            has_credentials: token.is_some(),
+                            expected_status: *expected_status,
+                            status,
+                        });
+                        match status_code_res {
                             Ok(status_code) => {
-                                if status_code != 200 {
+                                if status_code != *expected_status {
                                     let err_msg: String = format!(
-                                        "WARNING: Failed to call endpoint '{}'",
-                                        endpoint.route
+                                        "WARNING: Endpoint '{}' {} credentials returned {} instead of {}",
+                                        route, credentials, status_code, expected_status
                                     );
                                     PrintCommand::Issue.print_agent_message(
-                                        &self.attributes.position.as_str(),
+                                        self.attributes.position.as_str(),
                                         err_msg.as_str(),
                                     );
                                 }
@@ -279,22 +587,28 @@ impl SpecialFunctions for AgentBackendDev {
                                     .expect("Failed to kill backend web server");
                                 let err_msg: String = format!("Error, checking backend '{}'", e);
                                 PrintCommand::Issue.print_agent_message(
-                                    &self.attributes.position.as_str(),
+                                    self.attributes.position.as_str(),
                                     err_msg.as_str(),
                                 );
                             }
                         }
                     }
-                    save_api_endpoints(&api_endpoints_str);
+                    save_api_endpoints(
+                        &serde_json::to_string_pretty(&api_endpoints)
+                            .expect("Failed to encode API Endpoints"),
+                    );
                     PrintCommand::UnitTest.print_agent_message(
-                        &self.attributes.position.as_str(),
+                        self.attributes.position.as_str(),
                         "Unit Testing: Backend testing is completed !",
                     );
                     run_backend_server
                         .kill()
                         .expect("Kill backend web server on completion");
+                    run_backend_server
+                        .wait()
+                        .expect("Failed to wait for backend web server");
 
-                    self.attributes.state = AgentState::Finished;
+                    self.attributes.update_state(AgentState::Finished);
                     continue;
                 }
                 _ => {
@@ -312,11 +626,55 @@ mod tests {
     use crate::models::agent_basic::basic_agent::AgentState;
     use crate::models::agents::agent_traits::FactSheet;
     use crate::models::agents::agent_traits::{ProjectScope, SpecialFunctions};
-    use crate::models::general::llm::Message;
+    use crate::models::general::config::AppConfig;
+
+    #[test]
+    fn test_route_objects_default_to_unprotected() {
+        let api_schema_str = std::fs::read_to_string("schemas/api_schema.json")
+            .expect("Failed to read schemas/api_schema.json");
+        let routes: Vec<RouteObject> = serde_json::from_str(&api_schema_str).unwrap();
+        assert!(routes
+            .iter()
+            .all(|route| route.is_route_protected.is_empty()));
+    }
+
+    #[test]
+    fn test_is_user_login_required() {
+        let mut factsheet: FactSheet = serde_json::from_str(
+            r#"{ "project_description": "build a website with user accounts", "project_scope": null,
+                 "external_urls": null, "backend_code": null, "api_endpoint_schema": null }"#,
+        )
+        .unwrap();
+        assert!(!is_user_login_required(&factsheet));
+
+        factsheet.project_scope = Some(ProjectScope {
+            is_crud_required: false,
+            is_user_login_and_logout: true,
+            is_external_urls_required: false,
+        });
+        assert!(is_user_login_required(&factsheet));
+    }
+
+    #[test]
+    fn test_has_no_usable_external_urls() {
+        let mut factsheet: FactSheet = serde_json::from_str(
+            r#"{ "project_description": "build a website that shows stock prices",
+                 "project_scope": { "is_crud_required": false, "is_user_login_and_logout": false,
+                                    "is_external_urls_required": true },
+                 "external_urls": [], "backend_code": null, "api_endpoint_schema": null }"#,
+        )
+        .unwrap();
+        assert!(has_no_usable_external_urls(&factsheet));
+
+        factsheet.external_urls = Some(vec!["https://api.kraken.com/0/public/Time".to_string()]);
+        assert!(!has_no_usable_external_urls(&factsheet));
+    }
 
     #[tokio::test]
     async fn test_writing_backend_code() {
-        let mut agent = AgentBackendDev::new();
+        let context = AppContext::new(AppConfig::load(&[]).expect("Invalid configuration"))
+            .expect("Failed to create context");
+        let mut agent = AgentBackendDev::new(Arc::new(context));
         // let mut factsheet = FactSheet {
         //     project_description: "build a website that displays current cryptocurrency prices (BTC, ETH, SOL, etc.) and provides time based on internet timezone.".to_string(),
         //     project_scope: Some(
@@ -340,7 +698,7 @@ mod tests {
         //     api_endpoint_schema: None,
         // };
         // dbg!(factsheet);
-        
+
         // let factsheet_str: &str =r#"
         // {
         //   "project_description": "build a website that fetches and tracks fitness progress with timezone information",
@@ -356,7 +714,7 @@ mod tests {
         //   "api_endpoint_schema": null
         // }"#;
 
-        let factsheet_str: &str =r#"
+        let factsheet_str: &str = r#"
         {
           "project_description": "build a website which returns current time",
           "project_scope": {
@@ -0,0 +1,385 @@
+use crate::ai_functions::aifunc_database::{
+    print_database_entities, print_fixed_migrations, print_persistence_module,
+    print_sqlite_migrations,
+};
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{
+    agent_task_request, agent_task_request_decoded, ensure_web_server_dependency, save_migrations,
+    save_persistence_module, DATABASE_VERIFY_PATH, MIGRATIONS_INCLUDE_PATH,
+};
+use crate::helpers::prompts::PromptInput;
+use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_trait::BasicTraits;
+use crate::models::agents::agent_traits::{
+    AgentError, DatabaseSchema, EntityObject, FactSheet, FactSheetField, SpecialFunctions,
+};
+use crate::models::general::app_context::AppContext;
+
+use async_trait::async_trait;
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::Arc;
+
+const RUSQLITE_DEPENDENCY: &str = r#"rusqlite = { version = "0.31.0", features = ["bundled"] }"#;
+
+// Database Engineer: designs the schema and the persistence layer of the backend
+#[derive(Debug)]
+pub struct AgentDatabase {
+    attributes: BasicAgent,
+    context: Arc<AppContext>,
+    bug_errors: Option<String>,
+    bug_count: u8,
+}
+
+impl AgentDatabase {
+    pub fn new(context: Arc<AppContext>) -> Self {
+        let attributes = BasicAgent::new(
+            "Design the database schema and persistence layer of the website".to_string(),
+            "Database Engineer".to_string(),
+        );
+        Self {
+            attributes,
+            context,
+            bug_errors: None,
+            bug_count: 0,
+        }
+    }
+
+    async fn call_database_entities(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<Vec<EntityObject>, AgentError> {
+        let input = PromptInput::new().with(
+            "PROJECT_DESCRIPTION",
+            factsheet.project_description.as_str(),
+        );
+        let entities: Vec<EntityObject> = agent_task_request_decoded(
+            &self.context,
+            &mut self.attributes,
+            input,
+            get_function_string!(print_database_entities),
+            print_database_entities,
+            false,
+        )
+        .await?;
+        Ok(entities)
+    }
+
+    async fn call_sqlite_migrations(
+        &mut self,
+        entities: &[EntityObject],
+    ) -> Result<String, AgentError> {
+        let input = PromptInput::new().with(
+            "DATABASE_ENTITIES",
+            serde_json::to_string(entities).expect("Failed to encode database entities"),
+        );
+        agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
+            get_function_string!(print_sqlite_migrations),
+            print_sqlite_migrations,
+            false,
+        )
+        .await
+        .map_err(AgentError::from)
+    }
+
+    async fn call_fix_migrations(&mut self, factsheet: &mut FactSheet) -> Result<(), AgentError> {
+        let schema = factsheet
+            .database_schema
+            .as_mut()
+            .expect("No database schema found on factsheet");
+        self.context.metrics.record_fix_round();
+        let input = PromptInput::new()
+            .with("BROKEN_MIGRATIONS", format!("{:?}", schema.migrations))
+            .with(
+                "DATABASE_ENTITIES",
+                serde_json::to_string(&schema.entities)
+                    .expect("Failed to encode database entities"),
+            )
+            .with("ERROR_BUGS", format!("{:?}", self.bug_errors));
+        let migrations: String = agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
+            get_function_string!(print_fixed_migrations),
+            print_fixed_migrations,
+            true,
+        )
+        .await?;
+
+        save_migrations(&migrations);
+        schema.migrations = migrations;
+        Ok(())
+    }
+
+    async fn call_persistence_module(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), AgentError> {
+        let schema = factsheet
+            .database_schema
+            .as_mut()
+            .expect("No database schema found on factsheet");
+        let input = PromptInput::new()
+            .with(
+                "DATABASE_ENTITIES",
+                serde_json::to_string(&schema.entities)
+                    .expect("Failed to encode database entities"),
+            )
+            .with("MIGRATIONS_PATH", MIGRATIONS_INCLUDE_PATH);
+        let persistence_module: String = agent_task_request(
+            &self.context,
+            &mut self.attributes,
+            input,
+            get_function_string!(print_persistence_module),
+            print_persistence_module,
+            false,
+        )
+        .await?;
+
+        save_persistence_module(&persistence_module);
+        schema.persistence_module = Some(persistence_module);
+        Ok(())
+    }
+}
+
+// Apply the migrations to a fresh SQLite file and check every entity was created with its columns
+pub fn verify_migrations(
+    db_path: &Path,
+    migrations: &str,
+    entities: &[EntityObject],
+) -> Result<(), String> {
+    if db_path.exists() {
+        std::fs::remove_file(db_path).map_err(|e| e.to_string())?;
+    }
+    if let Some(dir) = db_path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    // Applied twice: the persistence module runs the migrations on every start
+    conn.execute_batch(migrations)
+        .map_err(|e| format!("Failed to apply migrations: {}", e))?;
+    conn.execute_batch(migrations)
+        .map_err(|e| format!("Migrations cannot be applied twice: {}", e))?;
+
+    let mut issues: Vec<String> = Vec::new();
+    for entity in entities {
+        let mut stmt = conn
+            .prepare("SELECT name FROM pragma_table_info(?1)")
+            .map_err(|e| e.to_string())?;
+        let columns: Vec<String> = stmt
+            .query_map([&entity.name], |row| row.get(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        if columns.is_empty() {
+            issues.push(format!("Table '{}' was not created", entity.name));
+            continue;
+        }
+        for column in &entity.columns {
+            if !columns.contains(&column.name) {
+                issues.push(format!(
+                    "Column '{}' is missing in table '{}'",
+                    column.name, entity.name
+                ));
+            }
+        }
+    }
+
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues.join("\n"))
+    }
+}
+
+#[async_trait]
+impl SpecialFunctions for AgentDatabase {
+    fn get_attributes_from_agent(&self) -> &BasicAgent {
+        &self.attributes
+    }
+
+    fn required_fields(&self) -> Vec<FactSheetField> {
+        vec![
+            FactSheetField::ProjectDescription,
+            FactSheetField::ProjectScope,
+        ]
+    }
+
+    fn produced_fields(&self) -> Vec<FactSheetField> {
+        vec![FactSheetField::DatabaseSchema]
+    }
+
+    fn reset(&mut self) {
+        self.attributes.reset();
+        self.bug_errors = None;
+        self.bug_count = 0;
+    }
+
+    async fn execute(
+        &mut self,
+        factsheet: &mut FactSheet,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        while self.attributes.state != AgentState::Finished {
+            self.attributes.open_state_span();
+            match self.attributes.state {
+                AgentState::Discovery => {
+                    let is_crud_required = factsheet
+                        .project_scope
+                        .as_ref()
+                        .map(|scope| scope.is_crud_required)
+                        .unwrap_or(false);
+                    if !is_crud_required {
+                        PrintCommand::AICall.print_agent_message(
+                            self.attributes.position.as_str(),
+                            "No CRUD required, the backend does not need a database",
+                        );
+                        self.attributes.update_state(AgentState::Finished);
+                        continue;
+                    }
+
+                    let entities = self.call_database_entities(factsheet).await?;
+                    let migrations = self.call_sqlite_migrations(&entities).await?;
+                    save_migrations(&migrations);
+                    factsheet.database_schema = Some(DatabaseSchema {
+                        entities,
+                        migrations,
+                        persistence_module: None,
+                    });
+
+                    self.attributes.update_state(AgentState::UnitTesting);
+                }
+
+                // Migrations are applied against a local SQLite file before the backend relies on them
+                AgentState::UnitTesting => {
+                    PrintCommand::UnitTest.print_agent_message(
+                        self.attributes.position.as_str(),
+                        "Database Unit Testing: applying migrations to a local SQLite file ...",
+                    );
+
+                    let schema = factsheet
+                        .database_schema
+                        .as_ref()
+                        .expect("No database schema found on factsheet");
+                    match verify_migrations(
+                        Path::new(DATABASE_VERIFY_PATH),
+                        &schema.migrations,
+                        &schema.entities,
+                    ) {
+                        Ok(()) => {
+                            self.bug_count = 0;
+                            PrintCommand::UnitTest.print_agent_message(
+                                self.attributes.position.as_str(),
+                                "Database Unit Testing: migrations applied successfully",
+                            );
+                            self.attributes.update_state(AgentState::Working);
+                        }
+                        Err(error_str) => {
+                            PrintCommand::Issue.print_agent_message(
+                                self.attributes.position.as_str(),
+                                error_str.as_str(),
+                            );
+                            self.bug_count += 1;
+                            self.bug_errors = Some(error_str);
+
+                            if self.bug_count > self.context.config.max_bug_fixes {
+                                PrintCommand::Issue.print_agent_message(
+                                    self.attributes.position.as_str(),
+                                    "Database Unit Testing: Exit, too many bugs in the migrations !",
+                                );
+                                return Err(Box::new(AgentError::Retryable(
+                                    "too many bugs in the migrations".to_string(),
+                                )));
+                            }
+
+                            self.call_fix_migrations(factsheet).await?;
+                        }
+                    }
+                }
+
+                // Migrations are verified: write the module the backend must use
+                AgentState::Working => {
+                    self.call_persistence_module(factsheet).await?;
+                    ensure_web_server_dependency(RUSQLITE_DEPENDENCY);
+                    self.attributes.update_state(AgentState::Finished);
+                }
+
+                _ => {
+                    self.attributes.update_state(AgentState::Finished);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::agents::agent_traits::ColumnObject;
+
+    fn todo_entity() -> EntityObject {
+        let column = |name: &str, data_type: &str, is_primary_key: bool| ColumnObject {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            is_primary_key,
+            is_nullable: false,
+        };
+        EntityObject {
+            name: "todo_items".to_string(),
+            columns: vec![
+                column("id", "INTEGER", true),
+                column("title", "TEXT", false),
+                column("completed", "INTEGER", false),
+            ],
+        }
+    }
+
+    fn verify_db_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("{}_{}.db", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_verify_migrations() {
+        let migrations = "CREATE TABLE IF NOT EXISTS todo_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            completed INTEGER NOT NULL
+        );";
+
+        let db_path = verify_db_path("verify_ok");
+        verify_migrations(&db_path, migrations, &[todo_entity()]).expect("Migrations should apply");
+        std::fs::remove_file(db_path).ok();
+    }
+
+    #[test]
+    fn test_verify_migrations_reports_missing_column() {
+        let migrations = "CREATE TABLE IF NOT EXISTS todo_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL
+        );";
+
+        let db_path = verify_db_path("verify_missing");
+        let err = verify_migrations(&db_path, migrations, &[todo_entity()]).unwrap_err();
+        assert!(err.contains("Column 'completed' is missing in table 'todo_items'"));
+        std::fs::remove_file(db_path).ok();
+    }
+
+    #[test]
+    fn test_verify_migrations_rejects_non_idempotent_migrations() {
+        let migrations =
+            "CREATE TABLE todo_items (id INTEGER PRIMARY KEY, title TEXT, completed INTEGER);";
+
+        let db_path = verify_db_path("verify_twice");
+        let err = verify_migrations(&db_path, migrations, &[todo_entity()]).unwrap_err();
+        assert!(err.starts_with("Migrations cannot be applied twice"));
+        std::fs::remove_file(db_path).ok();
+    }
+}
@@ -0,0 +1,368 @@
+use crate::ai_functions::aifunc_security::print_security_review;
+use crate::helpers::command_line::PrintCommand;
+use crate::helpers::general::{agent_task_request_decoded, read_web_server_sources};
+use crate::helpers::prompts::PromptInput;
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agents::agent_traits::{
+    AgentError, FactSheet, SecurityFinding, SecurityReport, Severity,
+};
+use crate::models::general::app_context::AppContext;
+
+use std::sync::Arc;
+
+// Words which make a string literal assigned next to them look like a credential
+const SECRET_HINTS: [&str; 6] = [
+    "api_key",
+    "apikey",
+    "secret",
+    "password",
+    "token",
+    "private_key",
+];
+const SECRET_PREFIXES: [&str; 4] = ["sk-", "ghp_", "xoxb-", "AKIA"];
+
+// What to do with AI code once the security review is done
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ApprovalPolicy {
+    // The user always reviews the code, findings are only printed
+    #[default]
+    Manual,
+    // High severity findings block execution, the user reviews everything else
+    Strict,
+    // Like Strict, but code without Medium findings runs without asking the user
+    Auto,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ApprovalDecision {
+    Approve,
+    AskUser,
+    Block,
+}
+
+impl ApprovalPolicy {
+    // "manual" (default), "strict" or "auto"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "manual" => Some(ApprovalPolicy::Manual),
+            "strict" => Some(ApprovalPolicy::Strict),
+            "auto" => Some(ApprovalPolicy::Auto),
+            _ => None,
+        }
+    }
+
+    // Nobody reviews the code of an unattended run, so Manual becomes Strict and High findings block
+    pub fn unattended(self) -> Self {
+        match self {
+            ApprovalPolicy::Manual => ApprovalPolicy::Strict,
+            policy => policy,
+        }
+    }
+
+    pub fn decide(&self, report: &SecurityReport) -> ApprovalDecision {
+        let highest_severity = report.highest_severity();
+        match (self, highest_severity) {
+            (ApprovalPolicy::Manual, _) => ApprovalDecision::AskUser,
+            (_, Some(Severity::High)) => ApprovalDecision::Block,
+            (ApprovalPolicy::Strict, _) => ApprovalDecision::AskUser,
+            (ApprovalPolicy::Auto, Some(Severity::Medium)) => ApprovalDecision::AskUser,
+            (ApprovalPolicy::Auto, _) => ApprovalDecision::Approve,
+        }
+    }
+}
+
+// Audits the generated workspace before anything is executed, a step of the agent which wrote the code
+#[derive(Debug)]
+pub struct SecurityReviewer {
+    context: Arc<AppContext>,
+}
+
+impl SecurityReviewer {
+    // The LLM review is added on top of the static scan with security_llm_review
+    pub fn new(context: Arc<AppContext>) -> Self {
+        Self { context }
+    }
+
+    async fn call_security_review(
+        &self,
+        attributes: &mut BasicAgent,
+        factsheet: &FactSheet,
+        static_findings: &[SecurityFinding],
+    ) -> Result<Vec<SecurityFinding>, AgentError> {
+        let input = PromptInput::new()
+            .with("CODE_INPUT", format!("{:?}", factsheet.backend_code))
+            .with(
+                "STATIC_FINDINGS",
+                serde_json::to_string(static_findings).expect("Failed to encode static findings"),
+            );
+        agent_task_request_decoded(
+            &self.context,
+            attributes,
+            input,
+            get_function_string!(print_security_review),
+            print_security_review,
+            false,
+        )
+        .await
+        .map_err(AgentError::from)
+    }
+
+    // Findings are reported and the LLM review is recorded under the agent being reviewed
+    pub async fn review(
+        &self,
+        attributes: &mut BasicAgent,
+        factsheet: &mut FactSheet,
+    ) -> Result<SecurityReport, AgentError> {
+        PrintCommand::UnitTest.print_agent_message(
+            attributes.position.as_str(),
+            "Security Review: scanning the generated code ...",
+        );
+
+        let mut findings: Vec<SecurityFinding> = read_web_server_sources()
+            .iter()
+            .flat_map(|(file, content)| scan_source(file, content))
+            .collect();
+
+        if self.context.config.is_security_llm_review {
+            let llm_findings = self
+                .call_security_review(attributes, factsheet, &findings)
+                .await?;
+            findings.extend(llm_findings);
+        }
+
+        for finding in &findings {
+            let finding_msg = format!(
+                "[{:?}] {}:{} {} - {}",
+                finding.severity, finding.file, finding.line, finding.rule, finding.message
+            );
+            let print_command = match finding.severity {
+                Severity::Low => PrintCommand::UnitTest,
+                Severity::Medium | Severity::High => PrintCommand::Issue,
+            };
+            print_command.print_agent_message(attributes.position.as_str(), &finding_msg);
+        }
+        if findings.is_empty() {
+            PrintCommand::UnitTest
+                .print_agent_message(attributes.position.as_str(), "Security Review: no findings");
+        }
+
+        let report = SecurityReport { findings };
+        factsheet.security_report = Some(report.clone());
+        Ok(report)
+    }
+}
+
+// String literals of a line of code, i.e. the parts between double quotes
+fn string_literals(line: &str) -> Vec<&str> {
+    line.split('"').skip(1).step_by(2).collect()
+}
+
+fn looks_like_secret(line: &str) -> bool {
+    let literals = string_literals(line);
+    if literals.iter().any(|literal| {
+        SECRET_PREFIXES
+            .iter()
+            .any(|prefix| literal.starts_with(prefix))
+    }) {
+        return true;
+    }
+
+    let lowercase_line = line.to_lowercase();
+    SECRET_HINTS.iter().any(|hint| {
+        lowercase_line.find(hint).is_some_and(|pos| {
+            let rest = &lowercase_line[pos + hint.len()..];
+            ["= \"", ": \""].iter().any(|marker| {
+                // The assigned value starts at the opening quote of the marker
+                let value = rest.find(marker).map(|i| &rest[i + marker.len() - 1..]);
+                value
+                    .and_then(|value| string_literals(value).first().copied())
+                    .is_some_and(|literal| {
+                        literal.len() >= 8 && !literal.contains(' ') && !literal.contains('{')
+                    })
+            })
+        })
+    })
+}
+
+// Static scan of one source file for dangerous patterns
+pub fn scan_source(file: &str, content: &str) -> Vec<SecurityFinding> {
+    let mut findings: Vec<SecurityFinding> = Vec::new();
+    let mut report = |line: usize, severity: Severity, rule: &str, message: &str| {
+        findings.push(SecurityFinding {
+            file: file.to_string(),
+            line,
+            severity,
+            rule: rule.to_string(),
+            message: message.to_string(),
+        });
+    };
+
+    for (index, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim();
+        let line_number = index + 1;
+        if line.starts_with("//") {
+            continue;
+        }
+
+        if line.contains("unsafe {")
+            || line.contains("unsafe{")
+            || line.contains("unsafe fn")
+            || line.contains("unsafe impl")
+        {
+            report(
+                line_number,
+                Severity::High,
+                "unsafe_code",
+                "Uses unsafe Rust",
+            );
+        }
+
+        if line.contains("process::Command") || line.contains("Command::new(") {
+            report(
+                line_number,
+                Severity::High,
+                "process_spawn",
+                "Spawns an external process",
+            );
+        }
+
+        if line.contains("fs::")
+            || line.contains("File::open")
+            || line.contains("File::create")
+            || line.contains("OpenOptions")
+        {
+            let is_outside_project = string_literals(line).iter().any(|literal| {
+                literal.starts_with('/') || literal.starts_with('~') || literal.contains("..")
+            });
+            if is_outside_project {
+                report(
+                    line_number,
+                    Severity::High,
+                    "filesystem_outside_project",
+                    "Accesses a path outside of the project directory",
+                );
+            } else {
+                report(
+                    line_number,
+                    Severity::Low,
+                    "filesystem_access",
+                    "Accesses the filesystem, check the path stays inside the project",
+                );
+            }
+        }
+
+        if looks_like_secret(line) {
+            report(
+                line_number,
+                Severity::Medium,
+                "hardcoded_secret",
+                "Looks like a hardcoded secret, read it from the environment instead",
+            );
+        }
+
+        if line.contains("web::Payload") || line.contains("limit(usize::MAX)") {
+            report(
+                line_number,
+                Severity::Medium,
+                "unbounded_request_body",
+                "Reads a request body without a size limit",
+            );
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(findings: &[SecurityFinding]) -> Vec<&str> {
+        findings
+            .iter()
+            .map(|finding| finding.rule.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_scan_source_flags_dangerous_patterns() {
+        let code = r#"
+use std::process::Command;
+// Command::new("rm") in a comment is fine
+fn main() {
+    let data = std::fs::read_to_string("/etc/passwd").unwrap();
+    let local = std::fs::read_to_string("data/items.json").unwrap();
+    let api_key = "abcdef1234567890";
+    let body = json!({ "username": "tester", "password": "hunter2hunter2" });
+    unsafe { std::ptr::null::<u8>().read() };
+}
+async fn upload(body: web::Payload) -> HttpResponse { HttpResponse::Ok().finish() }
+"#;
+        let findings = scan_source("src/main.rs", code);
+        assert_eq!(
+            rules(&findings),
+            vec![
+                "process_spawn",
+                "filesystem_outside_project",
+                "filesystem_access",
+                "hardcoded_secret",
+                "hardcoded_secret",
+                "unsafe_code",
+                "unbounded_request_body",
+            ]
+        );
+        assert_eq!(findings[0].line, 2);
+        assert_eq!(findings[1].severity, Severity::High);
+    }
+
+    #[test]
+    fn test_scan_source_ignores_vetted_auth_module() {
+        let auth_module = include_str!("../../../templates/auth.rs");
+        assert!(scan_source("src/auth.rs", auth_module).is_empty());
+    }
+
+    #[test]
+    fn test_approval_policy_decisions() {
+        let finding = |severity: Severity| SecurityFinding {
+            file: "src/main.rs".to_string(),
+            line: 1,
+            severity,
+            rule: "test".to_string(),
+            message: "test".to_string(),
+        };
+        let clean = SecurityReport::default();
+        let medium = SecurityReport {
+            findings: vec![finding(Severity::Low), finding(Severity::Medium)],
+        };
+        let high = SecurityReport {
+            findings: vec![finding(Severity::High)],
+        };
+
+        assert_eq!(
+            ApprovalPolicy::Manual.decide(&high),
+            ApprovalDecision::AskUser
+        );
+        assert_eq!(
+            ApprovalPolicy::Strict.decide(&high),
+            ApprovalDecision::Block
+        );
+        assert_eq!(
+            ApprovalPolicy::Strict.decide(&clean),
+            ApprovalDecision::AskUser
+        );
+        assert_eq!(
+            ApprovalPolicy::Auto.decide(&clean),
+            ApprovalDecision::Approve
+        );
+        assert_eq!(
+            ApprovalPolicy::Auto.decide(&medium),
+            ApprovalDecision::AskUser
+        );
+        assert_eq!(ApprovalPolicy::Auto.decide(&high), ApprovalDecision::Block);
+
+        // --yes with the default policy
+        let unattended = ApprovalPolicy::Manual.unattended();
+        assert_eq!(unattended.decide(&high), ApprovalDecision::Block);
+        assert_eq!(unattended.decide(&clean), ApprovalDecision::AskUser);
+        assert_eq!(ApprovalPolicy::Auto.unattended(), ApprovalPolicy::Auto);
+    }
+}
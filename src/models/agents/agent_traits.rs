@@ -1,11 +1,21 @@
+use crate::apis::call_request::LlmError;
 use crate::models::agent_basic::basic_agent::BasicAgent;
 use async_trait::async_trait;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{Schema, SchemaObject};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
+
+// Any JSON value, written as an empty schema rather than `true` which some providers reject
+fn any_json_schema(_: &mut SchemaGenerator) -> Schema {
+    Schema::Object(SchemaObject::default())
+}
 
 ///   {
 ///     "route": "/item/{id}",
 ///     "is_route_dynamic": "true",
+///     "is_route_protected": "false",
 ///     "method": "get"
 ///     "request_body": "None",
 ///     "response": {
@@ -17,6 +27,7 @@ use std::fmt::Debug;
 ///   {
 ///     "route": "/item",
 ///     "is_route_dynamic": "false",
+///     "is_route_protected": "true",
 ///     "method": "post",
 ///     "request_body": {
 ///       "id": "number",
@@ -26,39 +37,202 @@ use std::fmt::Debug;
 ///     "response": "None"
 ///   },
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct RouteObject {
     pub route: String,
     pub is_route_dynamic: String,
+    #[serde(default)]
+    pub is_route_protected: String,
     pub method: String,
+    #[schemars(schema_with = "any_json_schema")]
     pub request_body: serde_json::Value,
+    #[schemars(schema_with = "any_json_schema")]
     pub response: serde_json::Value,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ProjectScope {
     pub is_crud_required: bool,
     pub is_user_login_and_logout: bool,
     pub is_external_urls_required: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct ColumnObject {
+    pub name: String,
+    pub data_type: String,
+    pub is_primary_key: bool,
+    pub is_nullable: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct EntityObject {
+    pub name: String,
+    pub columns: Vec<ColumnObject>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DatabaseSchema {
+    pub entities: Vec<EntityObject>,
+    pub migrations: String,
+    pub persistence_module: Option<String>,
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
+pub struct SecurityFinding {
+    pub file: String,
+    pub line: usize,
+    pub severity: Severity,
+    pub rule: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SecurityReport {
+    pub findings: Vec<SecurityFinding>,
+}
+
+impl SecurityReport {
+    pub fn highest_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|finding| finding.severity).max()
+    }
+}
+
+// A question asked by the manager before the goal is set, with the user's answer
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Clarification {
+    pub question: String,
+    pub answer: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FactSheet {
     pub project_description: String,
+    #[serde(default)]
+    pub clarifications: Vec<Clarification>,
     pub project_scope: Option<ProjectScope>,
     pub external_urls: Option<Vec<String>>,
+    pub database_schema: Option<DatabaseSchema>,
     pub backend_code: Option<String>,
     pub api_endpoint_schema: Option<Vec<RouteObject>>,
+    pub security_report: Option<SecurityReport>,
+}
+
+// Fields of the FactSheet, used by agents to declare what they need and what they produce
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FactSheetField {
+    ProjectDescription,
+    ProjectScope,
+    ExternalUrls,
+    DatabaseSchema,
+    BackendCode,
+    ApiEndpointSchema,
+    SecurityReport,
+}
+
+impl FactSheet {
+    pub fn has_field(&self, field: FactSheetField) -> bool {
+        match field {
+            FactSheetField::ProjectDescription => !self.project_description.is_empty(),
+            FactSheetField::ProjectScope => self.project_scope.is_some(),
+            FactSheetField::ExternalUrls => self.external_urls.is_some(),
+            FactSheetField::DatabaseSchema => self.database_schema.is_some(),
+            FactSheetField::BackendCode => self.backend_code.is_some(),
+            FactSheetField::ApiEndpointSchema => self.api_endpoint_schema.is_some(),
+            FactSheetField::SecurityReport => self.security_report.is_some(),
+        }
+    }
+
+    // Copy one field written by an agent on its own copy of the FactSheet
+    pub fn copy_field_from(&mut self, other: &FactSheet, field: FactSheetField) {
+        match field {
+            FactSheetField::ProjectDescription => {
+                self.project_description = other.project_description.clone()
+            }
+            FactSheetField::ProjectScope => self.project_scope = other.project_scope.clone(),
+            FactSheetField::ExternalUrls => self.external_urls = other.external_urls.clone(),
+            FactSheetField::DatabaseSchema => self.database_schema = other.database_schema.clone(),
+            FactSheetField::BackendCode => self.backend_code = other.backend_code.clone(),
+            FactSheetField::ApiEndpointSchema => {
+                self.api_endpoint_schema = other.api_endpoint_schema.clone()
+            }
+            FactSheetField::SecurityReport => self.security_report = other.security_report.clone(),
+        }
+    }
+}
+
+// Errors agents return so that the manager can decide how to recover
+#[derive(Debug)]
+pub enum AgentError {
+    // The agent may succeed when it runs again from the same inputs
+    Retryable(String),
+    // An input is unusable: the agents producing it have to run again
+    BadInput {
+        field: FactSheetField,
+        reason: String,
+    },
+    // Nothing can be done, the project stops
+    Fatal(String),
 }
 
+impl Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgentError::Retryable(reason) => write!(f, "{}", reason),
+            AgentError::BadInput { field, reason } => {
+                write!(f, "{:?} is unusable: {}", field, reason)
+            }
+            AgentError::Fatal(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+// Errors left once call_gpt gave up retrying: a later round may get through a rate limit or an outage,
+// a rejected key or request fails the same way every time
+impl From<LlmError> for AgentError {
+    fn from(error: LlmError) -> Self {
+        if error.is_retryable() {
+            AgentError::Retryable(format!("LLM call failed: {}", error))
+        } else {
+            AgentError::Fatal(format!("LLM call failed: {}", error))
+        }
+    }
+}
+
+// Send + Sync: agents run on spawned tasks, possibly several projects at once
 #[async_trait]
-pub trait SpecialFunctions: Debug {
+pub trait SpecialFunctions: Debug + Send + Sync {
     // Used so that manager can get attributes from Agents
     fn get_attributes_from_agent(&self) -> &BasicAgent;
 
+    // FactSheet fields which must be set before the agent can start
+    fn required_fields(&self) -> Vec<FactSheetField>;
+
+    // FactSheet fields the agent uses when another agent produced them
+    fn optional_fields(&self) -> Vec<FactSheetField> {
+        Vec::new()
+    }
+
+    // FactSheet fields the agent writes, the manager schedules their readers after it
+    fn produced_fields(&self) -> Vec<FactSheetField>;
+
+    // Put the agent back in its initial state so that the manager can dispatch it again
+    fn reset(&mut self);
+
     // This function will allow agent to execute their logic
     async fn execute(
         &mut self,
         factsheet: &mut FactSheet,
-    ) -> Result<(), Box<dyn std::error::Error>>;
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
 }
@@ -0,0 +1,175 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::helpers::command_line::{review_checkpoint, user_input, CheckpointChoice, PrintCommand};
+use crate::helpers::dashboard;
+use crate::models::agents::agent_traits::FactSheet;
+use crate::models::general::app_context::AppContext;
+
+// Used when $EDITOR is not set
+const DEFAULT_EDITOR: &str = "vi";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io { path: PathBuf, message: String },
+    Editor(String),
+    Invalid { path: PathBuf, message: String },
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io { path, message } => {
+                write!(f, "cannot access {}: {}", path.display(), message)
+            }
+            CheckpointError::Editor(message) => write!(f, "editor failed: {}", message),
+            CheckpointError::Invalid { path, message } => {
+                write!(
+                    f,
+                    "{} is not a valid FactSheet: {}",
+                    path.display(),
+                    message
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+// The FactSheet written to a file once a scheduler level finished, so the user can correct it
+// before the next level uses it. Agents of one level run together and are reviewed together
+#[derive(Debug)]
+pub struct FactSheetCheckpoint {
+    path: PathBuf,
+    is_enabled: bool,
+}
+
+impl FactSheetCheckpoint {
+    pub fn new(path: PathBuf, is_enabled: bool) -> Self {
+        Self { path, is_enabled }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, factsheet: &FactSheet) -> Result<(), CheckpointError> {
+        let json = serde_json::to_string_pretty(factsheet).expect("FactSheet is always valid JSON");
+        std::fs::write(&self.path, json).map_err(|e| self.io_error(e))
+    }
+
+    // The edited file must still decode as a FactSheet
+    pub fn read(&self) -> Result<FactSheet, CheckpointError> {
+        let json = std::fs::read_to_string(&self.path).map_err(|e| self.io_error(e))?;
+        serde_json::from_str(&json).map_err(|e| CheckpointError::Invalid {
+            path: self.path.clone(),
+            message: e.to_string(),
+        })
+    }
+
+    // Asks the user once the agents of a level (`finished`) ran, skipping stops asking for good
+    pub fn review(&mut self, context: &AppContext, finished: &str, factsheet: &mut FactSheet) {
+        if !self.is_enabled {
+            return;
+        }
+        if let Err(e) = self.write(factsheet) {
+            PrintCommand::Issue.print_agent_message("Checkpoint", &e.to_string());
+            return;
+        }
+        // The editor needs the terminal too
+        let _terminal = dashboard::release_terminal();
+        loop {
+            let choice = user_input(context, &format!("Checkpoint after {}", finished), || {
+                review_checkpoint(finished, self.path())
+            });
+            match choice {
+                CheckpointChoice::Accept => return,
+                CheckpointChoice::Skip => {
+                    self.is_enabled = false;
+                    return;
+                }
+                CheckpointChoice::Edit => {
+                    // The edited FactSheet is recorded, a replay does not open the editor
+                    let edited_factsheet: Option<FactSheet> = user_input(
+                        context,
+                        &format!("FactSheet edited after {}", finished),
+                        || {
+                            self.edit()
+                                .map_err(|e| {
+                                    PrintCommand::Issue
+                                        .print_agent_message("Checkpoint", &e.to_string())
+                                })
+                                .ok()
+                        },
+                    );
+                    // Otherwise the user fixes the file or accepts the FactSheet as it was
+                    if let Some(edited_factsheet) = edited_factsheet {
+                        *factsheet = edited_factsheet;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    fn edit(&self) -> Result<FactSheet, CheckpointError> {
+        let editor = std::env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+        // $EDITOR may carry arguments, e.g. "code --wait"
+        let mut editor_words = editor.split_whitespace();
+        let program = editor_words
+            .next()
+            .ok_or_else(|| CheckpointError::Editor("$EDITOR is empty".to_string()))?;
+        let status = Command::new(program)
+            .args(editor_words)
+            .arg(&self.path)
+            .status()
+            .map_err(|e| CheckpointError::Editor(format!("{}: {}", program, e)))?;
+        if !status.success() {
+            return Err(CheckpointError::Editor(format!(
+                "{} exited with {}",
+                program, status
+            )));
+        }
+        self.read()
+    }
+
+    fn io_error(&self, e: std::io::Error) -> CheckpointError {
+        CheckpointError::Io {
+            path: self.path.clone(),
+            message: e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_reads_back_edits_and_rejects_invalid_json() {
+        let dir = std::env::temp_dir()
+            .join("auto_gippity_tests")
+            .join(format!("checkpoint_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let checkpoint = FactSheetCheckpoint::new(dir.join("factsheet.json"), true);
+
+        let mut factsheet: FactSheet =
+            serde_json::from_str(r#"{ "project_description": "build a todo app" }"#).unwrap();
+        checkpoint.write(&factsheet).unwrap();
+        assert_eq!(checkpoint.read().unwrap(), factsheet);
+
+        // What the user does in the editor
+        let edited = std::fs::read_to_string(checkpoint.path()).unwrap().replace(
+            r#""external_urls": null"#,
+            r#""external_urls": ["https://example.com"]"#,
+        );
+        std::fs::write(checkpoint.path(), edited).unwrap();
+        factsheet.external_urls = Some(vec!["https://example.com".to_string()]);
+        assert_eq!(checkpoint.read().unwrap(), factsheet);
+
+        std::fs::write(checkpoint.path(), r#"{ "project_scope": {} }"#).unwrap();
+        let err = checkpoint.read().unwrap_err().to_string();
+        assert!(err.contains("is not a valid FactSheet"), "{}", err);
+    }
+}
@@ -1,87 +1,908 @@
-use crate::models::agent_basic::basic_agent::{AgentState, BasicAgent};
+use crate::models::agent_basic::basic_agent::BasicAgent;
+use crate::models::agent_basic::basic_trait::BasicTraits;
 use crate::models::agents::agent_backend::AgentBackendDev;
-use crate::models::agents::agent_traits::{FactSheet, SpecialFunctions};
+use crate::models::agents::agent_traits::{
+    AgentError, Clarification, FactSheet, FactSheetField, ProjectScope, SpecialFunctions,
+};
 
-use crate::ai_functions::aifunc_managing::convert_user_input_to_goal;
-use crate::helpers::general::ai_task_request;
+use crate::ai_functions::aifunc_architect::print_project_scope;
+use crate::ai_functions::aifunc_managing::{
+    convert_user_input_to_goal, print_clarifying_questions,
+};
+use crate::apis::call_request::LlmError;
+use crate::helpers::command_line::{
+    get_use_response, review_project_brief, user_input, BriefReview, PrintCommand,
+};
+use crate::helpers::events;
+use crate::helpers::general::{agent_task_request, agent_task_request_decoded};
+use crate::helpers::prompts::PromptInput;
 use crate::models::agents::agent_architect::AgentSolutionArchitect;
-use crate::models::general::llm::Message;
+use crate::models::agents::agent_database::AgentDatabase;
+use crate::models::agents_manager::checkpoint::FactSheetCheckpoint;
+use crate::models::agents_manager::scheduler::{schedule_levels, AgentNode};
+use crate::models::general::app_context::AppContext;
+
+use futures::future::join_all;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{instrument, Instrument};
 
 #[derive(Debug)]
 pub struct ManagingAgent {
     attributes: BasicAgent,
+    context: Arc<AppContext>,
     factsheet: FactSheet,
     agents: Vec<Box<dyn SpecialFunctions>>,
+    max_rounds: u8,
+    checkpoint: FactSheetCheckpoint,
 }
 
 impl ManagingAgent {
-    pub async fn new(user_request: String) -> Result<Self, Box<dyn std::error::Error>> {
-        let position = "Project Manager".to_string();
-        let attributes = BasicAgent {
-            objective: "Manage the project to build excellent website for the user".to_string(),
-            position: position.clone(),
-            state: AgentState::Discovery,
-            memory: Vec::new(),
+    #[instrument(name = "manager.new", skip_all)]
+    pub async fn new(
+        context: Arc<AppContext>,
+        user_request: String,
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut attributes = BasicAgent::new(
+            "Manage the project to build excellent website for the user".to_string(),
+            "Project Manager".to_string(),
+        );
+        // Nobody answers when unattended, the request is used as it is
+        let is_interactive = context.config.is_clarifying && !context.config.is_unattended;
+        let clarifications = if is_interactive {
+            ask_clarifying_questions(&context, &mut attributes, &user_request).await?
+        } else {
+            Vec::new()
         };
-        let ai_response = ai_task_request(
-            user_request,
-            &position,
+
+        let mut input = PromptInput::new().with("USER_REQUEST", user_request);
+        if !clarifications.is_empty() {
+            input.push("CLARIFICATIONS", clarifications_text(&clarifications));
+        }
+        let ai_response = agent_task_request(
+            &context,
+            &mut attributes,
+            input,
             get_function_string!(convert_user_input_to_goal),
             convert_user_input_to_goal,
+            false,
         )
-        .await;
+        .await?;
 
-        let factsheet = FactSheet {
+        let mut factsheet = FactSheet {
             project_description: ai_response,
+            clarifications,
             project_scope: None,
             external_urls: None,
+            database_schema: None,
             backend_code: None,
             api_endpoint_schema: None,
+            security_report: None,
         };
+        if is_interactive {
+            confirm_project_brief(&context, &mut attributes, &mut factsheet).await?;
+        }
+
+        let max_rounds: u8 = context.config.max_orchestration_rounds;
+        let checkpoint = FactSheetCheckpoint::new(
+            context.config.checkpoint_path.clone(),
+            context.config.is_checkpointing && !context.config.is_unattended,
+        );
 
         let agents: Vec<Box<dyn SpecialFunctions>> = vec![];
         Ok(Self {
             attributes,
+            context,
             factsheet,
             agents,
+            max_rounds,
+            checkpoint,
         })
     }
 
+    pub fn factsheet(&self) -> &FactSheet {
+        &self.factsheet
+    }
+
     pub fn add_agent(&mut self, agent: Box<dyn SpecialFunctions>) {
         self.agents.push(agent);
     }
 
     fn create_agents(&mut self) {
-        self.add_agent(Box::new(AgentSolutionArchitect::new()));
-        self.add_agent(Box::new(AgentBackendDev::new()));        
+        self.add_agent(Box::new(AgentSolutionArchitect::new(self.context.clone())));
+        self.add_agent(Box::new(AgentDatabase::new(self.context.clone())));
+        self.add_agent(Box::new(AgentBackendDev::new(self.context.clone())));
 
         // Later on: can add anything: Testers, DevOps ...
     }
 
-    pub async fn execute_project(&mut self) {
-        self.create_agents();
+    // The agents' FactSheet contracts define the order, not their position in the vector
+    fn schedule_agents(&self) -> Result<Vec<Vec<usize>>, Box<dyn std::error::Error + Send + Sync>> {
+        let nodes: Vec<AgentNode> = self
+            .agents
+            .iter()
+            .map(|agent| AgentNode {
+                reads: [agent.required_fields(), agent.optional_fields()].concat(),
+                writes: agent.produced_fields(),
+            })
+            .collect();
+
+        schedule_levels(&nodes).map_err(|cycle| {
+            let positions: Vec<&str> = cycle
+                .iter()
+                .map(|&i| {
+                    self.agents[i]
+                        .get_attributes_from_agent()
+                        .get_position()
+                        .as_str()
+                })
+                .collect();
+            format!("Agents depend on each other: {}", positions.join(", ")).into()
+        })
+    }
+
+    fn check_required_inputs(
+        &self,
+        agent_indexes: &[usize],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Refuse to start an agent whose inputs were never produced
+        for &index in agent_indexes {
+            let agent = &self.agents[index];
+            for field in agent.required_fields() {
+                if !self.factsheet.has_field(field) {
+                    let position = agent.get_attributes_from_agent().get_position();
+                    return Err(format!(
+                        "{} cannot start: {:?} is missing on the factsheet",
+                        position, field
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Runs every agent in its own task, each one on its own copy of the FactSheet.
+    // Successful agents write their declared outputs to the shared FactSheet one at a time,
+    // a failed agent leaves it as it was before it started.
+    async fn run_agents(
+        &mut self,
+        agent_indexes: &[usize],
+    ) -> Vec<(usize, Result<(), Box<dyn std::error::Error + Send + Sync>>)> {
+        let shared_factsheet = Arc::new(Mutex::new(self.factsheet.clone()));
+        let mut agent_slots: Vec<Option<Box<dyn SpecialFunctions>>> =
+            std::mem::take(&mut self.agents)
+                .into_iter()
+                .map(Some)
+                .collect();
+
+        let mut handles = Vec::new();
+        for &index in agent_indexes {
+            let mut agent = agent_slots[index].take().expect("Agent scheduled twice");
+            let mut snapshot = self.factsheet.clone();
+            let shared_factsheet = shared_factsheet.clone();
+            // Created here, a spawned task would lose the manager span as its parent
+            let attributes = agent.get_attributes_from_agent();
+            let agent_span = tracing::info_span!(
+                "agent.execute",
+                agent = %attributes.get_position(),
+                objective = %attributes.get_objective()
+            );
+
+            // Events of a spawned task belong to no run unless it is wrapped again
+            handles.push(tokio::spawn(
+                events::in_run(self.context.run_id, async move {
+                    let agent_res = agent.execute(&mut snapshot).await;
+                    if agent_res.is_ok() {
+                        let mut factsheet = shared_factsheet.lock().await;
+                        for field in agent.produced_fields() {
+                            factsheet.copy_field_from(&snapshot, field);
+                        }
+                    }
+                    (index, agent, agent_res)
+                })
+                .instrument(agent_span),
+            ));
+        }
+        let joined = join_all(handles).await;
+
+        let mut agent_results = Vec::new();
+        for task_res in joined {
+            // An agent panicking still brings the whole project down, as before
+            let (index, agent, agent_res) =
+                task_res.unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()));
+            agent_slots[index] = Some(agent);
+            agent_results.push((index, agent_res));
+        }
+        self.agents = agent_slots
+            .into_iter()
+            .map(|slot| slot.expect("Agent lost while running"))
+            .collect();
+        self.factsheet = shared_factsheet.lock().await.clone();
+        agent_results
+    }
+
+    fn review_outcome(
+        &self,
+        index: usize,
+        agent_res: Result<(), Box<dyn std::error::Error + Send + Sync>>,
+    ) -> ReviewDecision {
+        let e = match agent_res {
+            Ok(()) => return ReviewDecision::Accept,
+            Err(e) => e,
+        };
+
+        let position = self.agents[index]
+            .get_attributes_from_agent()
+            .get_position();
+        let err_msg = format!("{} failed: {}", position, e);
+        PrintCommand::Issue.print_agent_message(&self.attributes.position, &err_msg);
+
+        match e.downcast_ref::<AgentError>() {
+            Some(AgentError::Fatal(reason)) => ReviewDecision::Abort(reason.clone()),
+            Some(AgentError::BadInput { field, .. }) => ReviewDecision::RerunUpstream(*field),
+            // Unexpected errors get another chance, like Retryable ones
+            Some(AgentError::Retryable(_)) | None => ReviewDecision::Retry,
+        }
+    }
+
+    fn use_round(
+        &mut self,
+        rounds: &mut u32,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Checked before counting, so the counter never goes past the limit
+        if *rounds >= u32::from(self.max_rounds) {
+            return Err(format!(
+                "Project stopped after {} orchestration rounds",
+                self.max_rounds
+            )
+            .into());
+        }
+        *rounds += 1;
+        let round_msg = format!(
+            "Starting orchestration round {}/{}",
+            rounds, self.max_rounds
+        );
+        PrintCommand::AICall.print_agent_message(&self.attributes.position, &round_msg);
+        Ok(())
+    }
+
+    #[instrument(name = "manager.execute_project", skip_all)]
+    pub async fn execute_project(
+        &mut self,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Agents added with add_agent replace the default team
+        if self.agents.is_empty() {
+            self.create_agents();
+        }
+        let levels = self.schedule_agents()?;
+
+        // FactSheet as it was before each level started, to roll back to
+        let mut level_snapshots: Vec<FactSheet> = Vec::new();
+        let mut rounds: u32 = 1;
+        let mut level_index: usize = 0;
+
+        while level_index < levels.len() {
+            level_snapshots.truncate(level_index);
+            level_snapshots.push(self.factsheet.clone());
+
+            let mut pending: Vec<usize> = levels[level_index].clone();
+            let mut rerun_from_level: Option<usize> = None;
+
+            while !pending.is_empty() {
+                self.check_required_inputs(&pending)?;
+
+                let mut retry: Vec<usize> = Vec::new();
+                for (index, agent_res) in self.run_agents(&pending).await {
+                    match self.review_outcome(index, agent_res) {
+                        ReviewDecision::Accept => {}
+                        ReviewDecision::Retry => retry.push(index),
+                        ReviewDecision::RerunUpstream(field) => {
+                            // Go back to the earliest level producing the unusable field
+                            let producer_level = levels[..level_index].iter().position(|level| {
+                                level
+                                    .iter()
+                                    .any(|&i| self.agents[i].produced_fields().contains(&field))
+                            });
+                            match producer_level {
+                                Some(producer_level) => {
+                                    rerun_from_level = Some(
+                                        rerun_from_level
+                                            .map_or(producer_level, |l| l.min(producer_level)),
+                                    )
+                                }
+                                None => {
+                                    return Err(
+                                        format!("No agent can produce {:?} again", field).into()
+                                    )
+                                }
+                            }
+                        }
+                        ReviewDecision::Abort(reason) => return Err(reason.into()),
+                    }
+                }
+
+                if rerun_from_level.is_some() {
+                    break;
+                }
+                if !retry.is_empty() {
+                    self.use_round(&mut rounds)?;
+                    for &index in &retry {
+                        self.agents[index].reset();
+                    }
+                }
+                pending = retry;
+            }
+
+            match rerun_from_level {
+                Some(target_level) => {
+                    self.use_round(&mut rounds)?;
+                    self.factsheet = level_snapshots[target_level].clone();
+                    for level in &levels[target_level..=level_index] {
+                        for &index in level {
+                            self.agents[index].reset();
+                        }
+                    }
+                    level_index = target_level;
+                }
+                None => {
+                    // Reviewed between levels, nothing left to run after the last one
+                    if level_index + 1 < levels.len() {
+                        let finished: Vec<&str> = levels[level_index]
+                            .iter()
+                            .map(|&i| {
+                                self.agents[i]
+                                    .get_attributes_from_agent()
+                                    .get_position()
+                                    .as_str()
+                            })
+                            .collect();
+                        self.checkpoint.review(
+                            &self.context,
+                            &finished.join(", "),
+                            &mut self.factsheet,
+                        );
+                    }
+                    level_index += 1
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Follow-up questions on what the request leaves open, an empty answer skips the question
+async fn ask_clarifying_questions(
+    context: &AppContext,
+    attributes: &mut BasicAgent,
+    user_request: &str,
+) -> Result<Vec<Clarification>, LlmError> {
+    let max_questions = usize::from(context.config.max_clarifying_questions);
+    if max_questions == 0 {
+        return Ok(Vec::new());
+    }
+    let input = PromptInput::new()
+        .with("USER_REQUEST", user_request)
+        .with("MAX_QUESTIONS", max_questions.to_string());
+    let questions: Vec<String> = agent_task_request_decoded(
+        context,
+        attributes,
+        input,
+        get_function_string!(print_clarifying_questions),
+        print_clarifying_questions,
+        false,
+    )
+    .await?;
+
+    let mut clarifications = Vec::new();
+    for question in questions.into_iter().take(max_questions) {
+        let answer: String = user_input(context, &question, || get_use_response(&question));
+        if !answer.is_empty() {
+            clarifications.push(Clarification { question, answer });
+        }
+    }
+    Ok(clarifications)
+}
+
+fn clarifications_text(clarifications: &[Clarification]) -> String {
+    clarifications
+        .iter()
+        .map(|clarification| format!("Q: {} A: {}", clarification.question, clarification.answer))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// The user confirms or edits the goal and the scope, the architect then keeps this scope
+async fn confirm_project_brief(
+    context: &AppContext,
+    attributes: &mut BasicAgent,
+    factsheet: &mut FactSheet,
+) -> Result<(), LlmError> {
+    let mut scope =
+        preview_project_scope(context, attributes, &factsheet.project_description).await?;
+    loop {
+        let review = user_input(context, "Project brief", || {
+            review_project_brief(&factsheet.project_description, &scope)
+        });
+        match review {
+            BriefReview::Confirm => break,
+            BriefReview::EditGoal => {
+                let goal: String =
+                    user_input(context, "New goal:", || get_use_response("New goal:"));
+                if !goal.is_empty() {
+                    factsheet.project_description = goal;
+                    scope =
+                        preview_project_scope(context, attributes, &factsheet.project_description)
+                            .await?;
+                }
+            }
+            BriefReview::EditScope => {
+                let scope_prompt = "Scope, e.g. crud=yes login=no urls=yes:";
+                let scope_edit: String =
+                    user_input(context, scope_prompt, || get_use_response(scope_prompt));
+                match edit_project_scope(&scope, &scope_edit) {
+                    Ok(edited_scope) => scope = edited_scope,
+                    Err(e) => PrintCommand::Issue.print_agent_message(&attributes.position, &e),
+                }
+            }
+        }
+    }
+    factsheet.project_scope = Some(scope);
+    Ok(())
+}
+
+async fn preview_project_scope(
+    context: &AppContext,
+    attributes: &mut BasicAgent,
+    goal: &str,
+) -> Result<ProjectScope, LlmError> {
+    agent_task_request_decoded(
+        context,
+        attributes,
+        PromptInput::new().with("PROJECT_DESCRIPTION", goal),
+        get_function_string!(print_project_scope),
+        print_project_scope,
+        false,
+    )
+    .await
+}
 
-        // Question: so the order of agent in the vector is important ?
-        // Because, i.e: Backend agent should be called after the architect agent
-        for agent in self.agents.iter_mut() {
-            let agent_res: Result<(), Box<dyn std::error::Error>> =
-                agent.execute(&mut self.factsheet).await;
-            // let agent_info = agent.get_attributes_from_agent();
-            // dbg!(agent_info);
+// "crud=yes login=no urls=yes", flags left out keep their value
+fn edit_project_scope(scope: &ProjectScope, scope_edit: &str) -> Result<ProjectScope, String> {
+    let mut edited_scope = scope.clone();
+    for item in scope_edit.split_whitespace() {
+        let (name, value) = item
+            .split_once('=')
+            .ok_or_else(|| format!("{:?} is not name=value", item))?;
+        let value = match value.to_lowercase().as_str() {
+            "yes" | "true" => true,
+            "no" | "false" => false,
+            _ => return Err(format!("{:?} of {} is not yes or no", value, name)),
+        };
+        match name {
+            "crud" => edited_scope.is_crud_required = value,
+            "login" => edited_scope.is_user_login_and_logout = value,
+            "urls" => edited_scope.is_external_urls_required = value,
+            _ => {
+                return Err(format!(
+                    "unknown scope {:?}, expected crud, login or urls",
+                    name
+                ))
+            }
         }
     }
+    Ok(edited_scope)
+}
+
+// What the manager does with the outcome of an agent
+#[derive(Debug, PartialEq)]
+enum ReviewDecision {
+    Accept,
+    Retry,
+    RerunUpstream(FactSheetField),
+    Abort(String),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::agent_basic::basic_agent::AgentState;
+    use crate::models::general::config::AppConfig;
+    use async_trait::async_trait;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::Barrier;
+
+    // Agent writing fixed values without calling any LLM, after failing with the given errors
+    #[derive(Debug)]
+    struct FakeAgent {
+        attributes: BasicAgent,
+        required: Vec<FactSheetField>,
+        produced: Vec<FactSheetField>,
+        failures: Vec<AgentError>,
+        runs: Arc<AtomicUsize>,
+        barrier: Option<Arc<Barrier>>,
+    }
+
+    impl FakeAgent {
+        fn new(
+            position: &str,
+            required: Vec<FactSheetField>,
+            produced: Vec<FactSheetField>,
+        ) -> Self {
+            Self {
+                attributes: BasicAgent::new("Fake".to_string(), position.to_string()),
+                required,
+                produced,
+                failures: Vec::new(),
+                runs: Arc::new(AtomicUsize::new(0)),
+                barrier: None,
+            }
+        }
+
+        // The agent only finishes once all agents sharing the barrier are running
+        fn waiting_on(mut self, barrier: Arc<Barrier>) -> Self {
+            self.barrier = Some(barrier);
+            self
+        }
+
+        fn failing_with(mut self, failures: Vec<AgentError>) -> Self {
+            self.failures = failures;
+            self
+        }
+    }
+
+    #[async_trait]
+    impl SpecialFunctions for FakeAgent {
+        fn get_attributes_from_agent(&self) -> &BasicAgent {
+            &self.attributes
+        }
+
+        fn required_fields(&self) -> Vec<FactSheetField> {
+            self.required.clone()
+        }
+
+        fn produced_fields(&self) -> Vec<FactSheetField> {
+            self.produced.clone()
+        }
+
+        fn reset(&mut self) {
+            self.attributes.state = AgentState::Discovery;
+        }
+
+        async fn execute(
+            &mut self,
+            factsheet: &mut FactSheet,
+        ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if let Some(barrier) = &self.barrier {
+                barrier.wait().await;
+            }
+
+            // Written before failing: must never reach the manager's FactSheet
+            factsheet.backend_code = Some("fn main() { broken }".to_string());
+            if !self.failures.is_empty() {
+                return Err(Box::new(self.failures.remove(0)));
+            }
+
+            for field in &self.produced {
+                match field {
+                    FactSheetField::ProjectScope => {
+                        factsheet.project_scope = Some(ProjectScope {
+                            is_crud_required: true,
+                            is_user_login_and_logout: false,
+                            is_external_urls_required: false,
+                        })
+                    }
+                    FactSheetField::ExternalUrls => factsheet.external_urls = Some(vec![]),
+                    FactSheetField::BackendCode => {
+                        factsheet.backend_code = Some("fn main() {}".to_string())
+                    }
+                    _ => {}
+                }
+            }
+            self.attributes.state = AgentState::Finished;
+            Ok(())
+        }
+    }
+
+    fn offline_manager() -> ManagingAgent {
+        let factsheet: FactSheet = serde_json::from_str(
+            r#"{ "project_description": "build a website that lists todo items", "project_scope": null,
+                 "external_urls": null, "backend_code": null, "api_endpoint_schema": null }"#,
+        )
+        .unwrap();
+        // Offline: nothing is read from or written to the cache
+        let config = AppConfig {
+            is_cache_enabled: false,
+            ..AppConfig::default()
+        };
+        let max_rounds = config.max_orchestration_rounds;
+        ManagingAgent {
+            attributes: BasicAgent::new("Manage".to_string(), "Project Manager".to_string()),
+            context: Arc::new(AppContext::new(config).unwrap()),
+            factsheet,
+            agents: vec![],
+            max_rounds,
+            // Nobody answers in tests
+            checkpoint: FactSheetCheckpoint::new(PathBuf::new(), false),
+        }
+    }
+
+    #[test]
+    fn test_edit_project_scope() {
+        let scope = ProjectScope {
+            is_crud_required: true,
+            is_user_login_and_logout: false,
+            is_external_urls_required: false,
+        };
+        let edited_scope = edit_project_scope(&scope, " login=yes  urls=TRUE ").unwrap();
+        assert!(edited_scope.is_crud_required);
+        assert!(edited_scope.is_user_login_and_logout);
+        assert!(edited_scope.is_external_urls_required);
+        assert_eq!(edit_project_scope(&scope, "").unwrap(), scope);
+
+        assert_eq!(
+            edit_project_scope(&scope, "crud=maybe").unwrap_err(),
+            "\"maybe\" of crud is not yes or no"
+        );
+        assert!(edit_project_scope(&scope, "pages=yes").is_err());
+        assert!(edit_project_scope(&scope, "crud").is_err());
+    }
+
+    #[test]
+    fn test_clarifications_are_kept_on_the_factsheet() {
+        let clarifications = vec![Clarification {
+            question: "Should users log in?".to_string(),
+            answer: "no, it is public".to_string(),
+        }];
+        assert_eq!(
+            clarifications_text(&clarifications),
+            "Q: Should users log in? A: no, it is public"
+        );
+
+        let mut factsheet = offline_manager().factsheet;
+        assert!(factsheet.clarifications.is_empty());
+        factsheet.clarifications = clarifications;
+        let json = serde_json::to_string(&factsheet).unwrap();
+        assert_eq!(serde_json::from_str::<FactSheet>(&json).unwrap(), factsheet);
+    }
+
+    fn retryable() -> AgentError {
+        AgentError::Retryable("flaky".to_string())
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_merges_declared_outputs() {
+        let mut manager = offline_manager();
+        manager.add_agent(Box::new(FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope],
+        )));
+
+        manager.execute_project().await.unwrap();
+        assert!(manager.factsheet.project_scope.is_some());
+        assert!(manager.factsheet.backend_code.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_refuses_missing_inputs() {
+        let mut manager = offline_manager();
+        manager.add_agent(Box::new(FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ExternalUrls],
+            vec![FactSheetField::ProjectScope],
+        )));
+
+        let err = manager.execute_project().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Scope Agent cannot start: ExternalUrls is missing on the factsheet"
+        );
+        assert!(manager.factsheet.project_scope.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_runs_independent_agents_concurrently() {
+        let mut manager = offline_manager();
+        let barrier = Arc::new(Barrier::new(2));
+        manager.add_agent(Box::new(
+            FakeAgent::new(
+                "Scope Agent",
+                vec![FactSheetField::ProjectDescription],
+                vec![FactSheetField::ProjectScope],
+            )
+            .waiting_on(barrier.clone()),
+        ));
+        manager.add_agent(Box::new(
+            FakeAgent::new(
+                "Urls Agent",
+                vec![FactSheetField::ProjectDescription],
+                vec![FactSheetField::ExternalUrls],
+            )
+            .waiting_on(barrier),
+        ));
+
+        // Run one after the other, the first agent would wait forever on the barrier
+        tokio::time::timeout(Duration::from_secs(5), manager.execute_project())
+            .await
+            .expect("Independent agents did not run concurrently")
+            .unwrap();
+        assert!(manager.factsheet.project_scope.is_some());
+        assert!(manager.factsheet.external_urls.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_projects_run_on_spawned_tasks() {
+        // Several projects at once, each manager moved into its own task
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let mut manager = offline_manager();
+                manager.add_agent(Box::new(FakeAgent::new(
+                    "Scope Agent",
+                    vec![FactSheetField::ProjectDescription],
+                    vec![FactSheetField::ProjectScope],
+                )));
+                tokio::spawn(
+                    async move { manager.execute_project().await.map(|_| manager.factsheet) },
+                )
+            })
+            .collect();
+
+        for handle in handles {
+            let factsheet = handle.await.unwrap().unwrap();
+            assert!(factsheet.project_scope.is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_retries_and_rolls_back_failed_agent() {
+        let mut manager = offline_manager();
+        let agent = FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope],
+        )
+        .failing_with(vec![retryable()]);
+        let runs = agent.runs.clone();
+        manager.add_agent(Box::new(agent));
+
+        manager.execute_project().await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        assert!(manager.factsheet.project_scope.is_some());
+        assert!(manager.factsheet.backend_code.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_reruns_upstream_agent_on_bad_input() {
+        let mut manager = offline_manager();
+        let architect = FakeAgent::new(
+            "Architect",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope, FactSheetField::ExternalUrls],
+        );
+        let backend = FakeAgent::new(
+            "Backend",
+            vec![FactSheetField::ProjectScope, FactSheetField::ExternalUrls],
+            vec![FactSheetField::BackendCode],
+        )
+        .failing_with(vec![AgentError::BadInput {
+            field: FactSheetField::ExternalUrls,
+            reason: "no url responds".to_string(),
+        }]);
+        let (architect_runs, backend_runs) = (architect.runs.clone(), backend.runs.clone());
+        manager.add_agent(Box::new(backend));
+        manager.add_agent(Box::new(architect));
+
+        manager.execute_project().await.unwrap();
+        assert_eq!(architect_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(backend_runs.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            manager.factsheet.backend_code,
+            Some("fn main() {}".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_stops_after_max_rounds() {
+        let mut manager = offline_manager();
+        manager.max_rounds = 2;
+        let agent = FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope],
+        )
+        .failing_with(vec![retryable(), retryable(), retryable()]);
+        let runs = agent.runs.clone();
+        manager.add_agent(Box::new(agent));
+
+        let err = manager.execute_project().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Project stopped after 2 orchestration rounds"
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_stops_at_the_largest_round_limit() {
+        let mut manager = offline_manager();
+        manager.max_rounds = u8::MAX;
+        let agent = FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope],
+        )
+        .failing_with((0..300).map(|_| retryable()).collect());
+        let runs = agent.runs.clone();
+        manager.add_agent(Box::new(agent));
+
+        let err = manager.execute_project().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Project stopped after 255 orchestration rounds"
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 255);
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_aborts_on_fatal_error() {
+        let mut manager = offline_manager();
+        manager.add_agent(Box::new(
+            FakeAgent::new(
+                "Backend",
+                vec![FactSheetField::ProjectDescription],
+                vec![FactSheetField::BackendCode],
+            )
+            .failing_with(vec![AgentError::Fatal(
+                "the user stopped the project".to_string(),
+            )]),
+        ));
+
+        let err = manager.execute_project().await.unwrap_err();
+        assert_eq!(err.to_string(), "the user stopped the project");
+    }
+
+    #[tokio::test]
+    async fn test_execute_project_retries_rate_limits_and_aborts_on_rejected_key() {
+        let mut manager = offline_manager();
+        let agent = FakeAgent::new(
+            "Scope Agent",
+            vec![FactSheetField::ProjectDescription],
+            vec![FactSheetField::ProjectScope],
+        )
+        .failing_with(vec![
+            AgentError::from(LlmError::RateLimited {
+                retry_after: None,
+                message: "slow down".to_string(),
+            }),
+            AgentError::from(LlmError::Auth("invalid api key".to_string())),
+        ]);
+        let runs = agent.runs.clone();
+        manager.add_agent(Box::new(agent));
+
+        let err = manager.execute_project().await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "LLM call failed: authentication failed: invalid api key"
+        );
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
     #[tokio::test]
     async fn test_managing_agent() {
         let user_request = "build a website show current crypto currencies prices (BTC, ETH, SOL ....), and provides time based on internet timezone.".to_string();
-        let mut manager = ManagingAgent::new(user_request)
+        let context = AppContext::new(AppConfig::load(&[]).expect("Invalid configuration"))
+            .expect("Failed to create context");
+        let mut manager = ManagingAgent::new(Arc::new(context), user_request)
             .await
             .expect("Failed to create Managing Agent");
-        manager.execute_project().await;
+        manager
+            .execute_project()
+            .await
+            .expect("Failed to execute project");
         dbg!(manager.factsheet);
     }
 }
@@ -0,0 +1,90 @@
+use crate::models::agents::agent_traits::FactSheetField;
+
+// What an agent reads from and writes to the FactSheet
+#[derive(Debug, Clone)]
+pub struct AgentNode {
+    pub reads: Vec<FactSheetField>,
+    pub writes: Vec<FactSheetField>,
+}
+
+// Orders agents into levels: an agent only depends on agents of earlier levels,
+// so all agents of one level can run at the same time.
+// Returns the agent indexes of every level, or the indexes of the agents caught in a cycle.
+pub fn schedule_levels(nodes: &[AgentNode]) -> Result<Vec<Vec<usize>>, Vec<usize>> {
+    let depends_on = |reader: usize, writer: usize| {
+        reader != writer
+            && nodes[reader]
+                .reads
+                .iter()
+                .any(|field| nodes[writer].writes.contains(field))
+    };
+
+    let mut scheduled: Vec<bool> = vec![false; nodes.len()];
+    let mut levels: Vec<Vec<usize>> = Vec::new();
+
+    while scheduled.iter().any(|done| !done) {
+        let level: Vec<usize> = (0..nodes.len())
+            .filter(|&reader| !scheduled[reader])
+            .filter(|&reader| {
+                (0..nodes.len()).all(|writer| scheduled[writer] || !depends_on(reader, writer))
+            })
+            .collect();
+
+        if level.is_empty() {
+            let cycle: Vec<usize> = (0..nodes.len()).filter(|&i| !scheduled[i]).collect();
+            return Err(cycle);
+        }
+        for &index in &level {
+            scheduled[index] = true;
+        }
+        levels.push(level);
+    }
+    Ok(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FactSheetField::*;
+
+    fn node(reads: Vec<FactSheetField>, writes: Vec<FactSheetField>) -> AgentNode {
+        AgentNode { reads, writes }
+    }
+
+    #[test]
+    fn test_schedule_levels_orders_by_dependencies() {
+        // Declared in the "wrong" order on purpose: vector order no longer matters
+        let nodes = vec![
+            node(vec![BackendCode], vec![SecurityReport]),
+            node(
+                vec![ProjectScope, ExternalUrls, DatabaseSchema],
+                vec![BackendCode, ApiEndpointSchema],
+            ),
+            node(vec![ProjectScope], vec![DatabaseSchema]),
+            node(vec![ProjectDescription], vec![ProjectScope, ExternalUrls]),
+        ];
+        let levels = schedule_levels(&nodes).unwrap();
+        assert_eq!(levels, vec![vec![3], vec![2], vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn test_schedule_levels_groups_independent_agents() {
+        let nodes = vec![
+            node(vec![ProjectDescription], vec![ProjectScope]),
+            node(vec![ProjectDescription], vec![ExternalUrls]),
+            node(vec![ProjectScope, ExternalUrls], vec![BackendCode]),
+        ];
+        let levels = schedule_levels(&nodes).unwrap();
+        assert_eq!(levels, vec![vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn test_schedule_levels_detects_cycles() {
+        let nodes = vec![
+            node(vec![ProjectDescription], vec![ProjectScope]),
+            node(vec![BackendCode], vec![SecurityReport]),
+            node(vec![SecurityReport], vec![BackendCode]),
+        ];
+        assert_eq!(schedule_levels(&nodes), Err(vec![1, 2]));
+    }
+}
@@ -0,0 +1,87 @@
+use reqwest::{Client, Proxy};
+
+use crate::apis::call_request::LlmClient;
+use crate::apis::recorded_responses::RecordedResponses;
+use crate::helpers::events::{self, RunId};
+use crate::helpers::prompts::PromptLibrary;
+use crate::models::general::config::{AppConfig, ConfigError};
+use crate::models::general::run_metrics::RunMetrics;
+
+// Created once at startup and shared by the manager and every agent
+#[derive(Debug)]
+pub struct AppContext {
+    pub config: AppConfig,
+    // Url checks, through the configured proxy
+    pub http: Client,
+    // Endpoint checks of the generated server on localhost, never proxied
+    pub local_http: Client,
+    pub llm: LlmClient,
+    pub prompts: PromptLibrary,
+    pub metrics: RunMetrics,
+    // Set by the replay subcommand: LLM calls are answered from a recorded run
+    pub replay: Option<RecordedResponses>,
+    // Events emitted under it go to the event log of this run only, see events::in_run
+    pub run_id: RunId,
+}
+
+impl AppContext {
+    pub fn new(config: AppConfig) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let mut http_builder = Client::builder().timeout(config.http_timeout);
+        if let Some(proxy) = &config.http_proxy {
+            http_builder = http_builder.proxy(Proxy::all(proxy)?);
+        }
+        let http = http_builder.build()?;
+        let local_http = Client::builder()
+            .timeout(config.http_timeout)
+            .no_proxy()
+            .build()?;
+        let llm = LlmClient::new(&config)?;
+        // Template mistakes are reported at startup, like the rest of the configuration
+        let prompts = PromptLibrary::load(config.prompt_dirs())
+            .map_err(|e| ConfigError::Invalid(e.to_string()))?;
+        Ok(Self {
+            config,
+            http,
+            local_http,
+            llm,
+            prompts,
+            metrics: RunMetrics::default(),
+            replay: None,
+            run_id: events::new_run_id(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::general::check_status_code;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_local_checks_skip_the_proxy() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                    .await
+                    .ok();
+            }
+        });
+        // Nothing listens on the discard port: every proxied request fails
+        let config = AppConfig {
+            http_proxy: Some("http://127.0.0.1:9".to_string()),
+            ..AppConfig::default()
+        };
+        let context = AppContext::new(config).unwrap();
+
+        assert_eq!(
+            check_status_code(&context.local_http, &url).await.unwrap(),
+            204
+        );
+        assert!(check_status_code(&context.http, &url).await.is_err());
+    }
+}
@@ -0,0 +1,977 @@
+use dotenv::dotenv;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::apis::call_request::{LlmProvider, ModelSettings, RetryPolicy};
+use crate::helpers::command_line::StreamOutput;
+use crate::models::agents::agent_security::ApprovalPolicy;
+
+const DEFAULT_LLM_MODEL: &str = "gpt-4";
+const DEFAULT_LLM_TEMPERATURE: f32 = 0.1;
+const DEFAULT_CACHE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+const DEFAULT_MAX_ORCHESTRATION_ROUNDS: u8 = 3;
+const DEFAULT_MAX_BUG_FIXES: u8 = 2;
+const DEFAULT_SERVER_STARTUP_SECS: u64 = 5;
+const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_MAX_CLARIFYING_QUESTIONS: u8 = 4;
+// Cheap classification and extraction tasks are routed to it by default
+const SMALL_MODEL: &str = "gpt-4o-mini";
+const SMALL_MODEL_MAX_TOKENS: u32 = 1024;
+const SMALL_MODEL_AI_FUNCTIONS: [&str; 5] = [
+    "convert_user_input_to_goal",
+    "print_clarifying_questions",
+    "print_project_scope",
+    "print_site_urls",
+    "summarize_agent_memory",
+];
+
+// Looked up in the working directory, i.e. next to the project being generated
+pub const PROJECT_CONFIG_FILE: &str = "auto_gippity.toml";
+const USER_CONFIG_FILE: &str = "config.toml";
+// Prompt templates of the project, next to auto_gippity.toml
+const PROJECT_PROMPTS_DIR: &str = "prompts";
+// FactSheet shown to the user between scheduler levels
+const DEFAULT_CHECKPOINT_FILE: &str = "factsheet.json";
+// One dir per run, holding its event log and final FactSheet
+const DEFAULT_RUNS_DIR: &str = "runs";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, message: String },
+    Parse { path: PathBuf, message: String },
+    Env { name: String, message: String },
+    Cli(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(f, "cannot read {}: {}", path.display(), message)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "invalid config file {}: {}", path.display(), message)
+            }
+            ConfigError::Env { name, message } => write!(f, "invalid {}: {}", name, message),
+            ConfigError::Cli(message) => write!(f, "invalid command line: {}", message),
+            ConfigError::Invalid(message) => write!(f, "invalid configuration: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// Route of one agent or one ai_function, unset values fall back to [llm]
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelOverride {
+    pub provider: Option<LlmProvider>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<u32>,
+    pub structured_output: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LlmLayer {
+    provider: Option<LlmProvider>,
+    model: Option<String>,
+    temperature: Option<f32>,
+    max_attempts: Option<u32>,
+    stream_output: Option<String>,
+    structured_output: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UiLayer {
+    dashboard: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CacheLayer {
+    enabled: Option<bool>,
+    replay_only: Option<bool>,
+    ttl_secs: Option<u64>,
+    dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SecurityLayer {
+    approval_policy: Option<String>,
+    llm_review: Option<bool>,
+    unattended: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiscoveryLayer {
+    clarify: Option<bool>,
+    max_questions: Option<u8>,
+    checkpoints: Option<bool>,
+    checkpoint_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LimitsLayer {
+    max_orchestration_rounds: Option<u8>,
+    max_bug_fixes: Option<u8>,
+    server_startup_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunsLayer {
+    dir: Option<PathBuf>,
+    traces: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HttpLayer {
+    timeout_secs: Option<u64>,
+    proxy: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PromptsLayer {
+    dir: Option<PathBuf>,
+    project_dir: Option<PathBuf>,
+    version: Option<String>,
+}
+
+// One source of settings: a config file, the environment or the command line
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    #[serde(default)]
+    llm: LlmLayer,
+    #[serde(default)]
+    ui: UiLayer,
+    #[serde(default)]
+    cache: CacheLayer,
+    #[serde(default)]
+    security: SecurityLayer,
+    #[serde(default)]
+    discovery: DiscoveryLayer,
+    #[serde(default)]
+    limits: LimitsLayer,
+    #[serde(default)]
+    runs: RunsLayer,
+    #[serde(default)]
+    http: HttpLayer,
+    #[serde(default)]
+    prompts: PromptsLayer,
+    // Keyed by agent position, e.g. "Backend Developer"
+    #[serde(default)]
+    agents: HashMap<String, ModelOverride>,
+    // Keyed by ai_function name, e.g. "print_backend_webserver_code"
+    #[serde(default)]
+    ai_functions: HashMap<String, ModelOverride>,
+}
+
+impl ConfigLayer {
+    pub fn from_toml(path: &Path, content: &str) -> Result<Self, ConfigError> {
+        toml::from_str(content).map_err(|e| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        })
+    }
+
+    // A missing file is an empty layer
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::from_toml(path, &content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Read {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv().ok();
+        let mut layer = Self::default();
+        layer.llm.model = env::var("LLM_MODEL").ok();
+        layer.llm.temperature = parsed_env_var("LLM_TEMPERATURE")?;
+        layer.llm.max_attempts = parsed_env_var("LLM_MAX_ATTEMPTS")?;
+        layer.llm.stream_output = env::var("STREAM_OUTPUT").ok();
+        layer.cache.ttl_secs = parsed_env_var("LLM_CACHE_TTL_SECS")?;
+        layer.cache.dir = env::var("AUTO_GIPPITY_CACHE_DIR").ok().map(PathBuf::from);
+        layer.security.approval_policy = env::var("APPROVAL_POLICY").ok();
+        layer.security.llm_review = env::var("SECURITY_LLM_REVIEW")
+            .ok()
+            .map(|llm_review| matches!(llm_review.as_str(), "true" | "1"));
+        layer.limits.max_orchestration_rounds = parsed_env_var("MAX_ORCHESTRATION_ROUNDS")?;
+        layer.limits.max_bug_fixes = parsed_env_var("MAX_BUG_FIXES")?;
+        layer.limits.server_startup_secs = parsed_env_var("SERVER_STARTUP_SECS")?;
+        layer.runs.dir = env::var("AUTO_GIPPITY_RUNS_DIR").ok().map(PathBuf::from);
+        layer.http.timeout_secs = parsed_env_var("HTTP_TIMEOUT_SECS")?;
+        layer.http.proxy = env::var("AUTO_GIPPITY_PROXY").ok();
+        layer.prompts.dir = env::var("AUTO_GIPPITY_PROMPTS_DIR").ok().map(PathBuf::from);
+        layer.prompts.version = env::var("PROMPT_VERSION").ok();
+        Ok(layer)
+    }
+
+    // Flags taking a value accept both "--flag value" and "--flag=value", --config is read by the caller
+    pub fn from_args(args: &[String]) -> Result<Self, ConfigError> {
+        let mut layer = Self::default();
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag, Some(value.to_string())),
+                None => (arg.as_str(), None),
+            };
+            let mut value = |flag: &str| {
+                inline_value
+                    .clone()
+                    .or_else(|| args_iter.next().cloned())
+                    .ok_or_else(|| ConfigError::Cli(format!("{} needs a value", flag)))
+            };
+
+            match flag {
+                "--no-cache" => layer.cache.enabled = Some(false),
+                "--dashboard" => layer.ui.dashboard = Some(true),
+                "--traces" => layer.runs.traces = Some(true),
+                "--recorded" => layer.cache.replay_only = Some(true),
+                "--yes" => layer.security.unattended = Some(true),
+                "--no-clarify" => layer.discovery.clarify = Some(false),
+                "--no-checkpoints" => layer.discovery.checkpoints = Some(false),
+                "--config" => {
+                    value(flag)?;
+                }
+                "--model" => layer.llm.model = Some(value(flag)?),
+                "--temperature" => layer.llm.temperature = Some(parsed_arg(flag, &value(flag)?)?),
+                "--approval-policy" => layer.security.approval_policy = Some(value(flag)?),
+                "--stream-output" => layer.llm.stream_output = Some(value(flag)?),
+                "--prompt-version" => layer.prompts.version = Some(value(flag)?),
+                "--max-rounds" => {
+                    layer.limits.max_orchestration_rounds = Some(parsed_arg(flag, &value(flag)?)?)
+                }
+                _ => return Err(ConfigError::Cli(format!("unknown argument {}", arg))),
+            }
+        }
+        Ok(layer)
+    }
+}
+
+fn parsed_env_var<T: FromStr>(name: &str) -> Result<Option<T>, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|e: T::Err| ConfigError::Env {
+                name: name.to_string(),
+                message: format!("{:?}: {}", value, e),
+            }),
+        Err(_) => Ok(None),
+    }
+}
+
+fn parsed_arg<T: FromStr>(flag: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e: T::Err| ConfigError::Cli(format!("{} {:?}: {}", flag, value, e)))
+}
+
+// Everything a run can be tuned with, read once at startup
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    pub llm_provider: LlmProvider,
+    pub llm_model: String,
+    pub llm_temperature: f32,
+    pub retry_policy: RetryPolicy,
+    pub stream_output: StreamOutput,
+    // JSON outputs are asked through tool calling, with the schema of the Rust type
+    pub is_structured_output: bool,
+    // Full screen dashboard instead of colored lines, only when stdout is a terminal
+    pub is_dashboard: bool,
+    pub is_cache_enabled: bool,
+    // Only recorded responses are used, a request missing from the cache fails
+    pub is_replay_only: bool,
+    pub cache_ttl: Duration,
+    // Data kept between runs: LLM responses and the knowledge base
+    pub cache_dir: PathBuf,
+    pub approval_policy: ApprovalPolicy,
+    pub is_security_llm_review: bool,
+    // Code the approval policy leaves to the user runs without asking, High findings still block,
+    // see ApprovalPolicy::unattended
+    pub is_unattended: bool,
+    // The manager asks follow-up questions and has the goal and scope confirmed, never when unattended
+    pub is_clarifying: bool,
+    pub max_clarifying_questions: u8,
+    // The user may edit the FactSheet between scheduler levels, never when unattended
+    pub is_checkpointing: bool,
+    pub checkpoint_path: PathBuf,
+    pub runs_dir: PathBuf,
+    // The spans of the run go to an OTLP JSON file in the run dir, see helpers::telemetry
+    pub is_exporting_traces: bool,
+    // Every retry or re-run of an agent uses a round
+    pub max_orchestration_rounds: u8,
+    // Failed builds or migrations an agent tries to fix before giving up
+    pub max_bug_fixes: u8,
+    // Wait between launching the generated server and calling its endpoints
+    pub server_startup_delay: Duration,
+    // Timeout of the url and endpoint checks, the LLM calls are not bound by it
+    pub http_timeout: Duration,
+    pub http_proxy: Option<String>,
+    // Prompt library shared by every project, see PromptLibrary
+    pub prompts_dir: PathBuf,
+    // Templates of this project only, they win over the library
+    pub project_prompts_dir: PathBuf,
+    // Subdir of both prompt dirs whose templates win over the unversioned ones
+    pub prompt_version: Option<String>,
+    // Built-in routes of the small ai_functions, below every configured layer
+    pub ai_function_defaults: HashMap<String, ModelOverride>,
+    pub agent_overrides: HashMap<String, ModelOverride>,
+    pub ai_function_overrides: HashMap<String, ModelOverride>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            llm_provider: LlmProvider::default(),
+            llm_model: DEFAULT_LLM_MODEL.to_string(),
+            llm_temperature: DEFAULT_LLM_TEMPERATURE,
+            retry_policy: RetryPolicy::default(),
+            stream_output: StreamOutput::default(),
+            is_structured_output: true,
+            is_dashboard: false,
+            is_cache_enabled: true,
+            is_replay_only: false,
+            cache_ttl: Duration::from_secs(DEFAULT_CACHE_TTL_SECS),
+            cache_dir: home_dir().join(".cache").join("auto_gippity"),
+            approval_policy: ApprovalPolicy::default(),
+            is_security_llm_review: false,
+            is_unattended: false,
+            is_clarifying: true,
+            max_clarifying_questions: DEFAULT_MAX_CLARIFYING_QUESTIONS,
+            is_checkpointing: true,
+            checkpoint_path: PathBuf::from(DEFAULT_CHECKPOINT_FILE),
+            runs_dir: PathBuf::from(DEFAULT_RUNS_DIR),
+            is_exporting_traces: false,
+            max_orchestration_rounds: DEFAULT_MAX_ORCHESTRATION_ROUNDS,
+            max_bug_fixes: DEFAULT_MAX_BUG_FIXES,
+            server_startup_delay: Duration::from_secs(DEFAULT_SERVER_STARTUP_SECS),
+            http_timeout: Duration::from_secs(DEFAULT_HTTP_TIMEOUT_SECS),
+            http_proxy: None,
+            prompts_dir: user_config_dir().join("prompts"),
+            project_prompts_dir: PathBuf::from(PROJECT_PROMPTS_DIR),
+            prompt_version: None,
+            ai_function_defaults: default_ai_function_routes(),
+            agent_overrides: HashMap::new(),
+            ai_function_overrides: HashMap::new(),
+        }
+    }
+}
+
+// Code generation stays on the [llm] model, which should be the strongest one
+fn default_ai_function_routes() -> HashMap<String, ModelOverride> {
+    SMALL_MODEL_AI_FUNCTIONS
+        .iter()
+        .map(|function_name| {
+            let route = ModelOverride {
+                model: Some(SMALL_MODEL.to_string()),
+                max_tokens: Some(SMALL_MODEL_MAX_TOKENS),
+                ..ModelOverride::default()
+            };
+            (function_name.to_string(), route)
+        })
+        .collect()
+}
+
+fn home_dir() -> PathBuf {
+    PathBuf::from(env::var("HOME").unwrap_or_else(|_| ".".to_string()))
+}
+
+// $XDG_CONFIG_HOME/auto_gippity, or ~/.config/auto_gippity
+fn user_config_dir() -> PathBuf {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| home_dir().join(".config"));
+    config_home.join("auto_gippity")
+}
+
+pub fn user_config_path() -> PathBuf {
+    user_config_dir().join(USER_CONFIG_FILE)
+}
+
+// Value of --config, the project file otherwise
+fn project_config_path(args: &[String]) -> PathBuf {
+    args.iter()
+        .enumerate()
+        .find_map(|(i, arg)| match arg.split_once('=') {
+            Some(("--config", path)) => Some(PathBuf::from(path)),
+            _ if arg == "--config" => args.get(i + 1).map(PathBuf::from),
+            _ => None,
+        })
+        .unwrap_or_else(|| PathBuf::from(PROJECT_CONFIG_FILE))
+}
+
+impl AppConfig {
+    // Defaults, then the user file, the project file, the environment and the command line
+    pub fn load(args: &[String]) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        config.apply(ConfigLayer::from_file(&user_config_path())?)?;
+        config.apply(ConfigLayer::from_file(&project_config_path(args))?)?;
+        config.apply(ConfigLayer::from_env()?)?;
+        config.apply(ConfigLayer::from_args(args)?)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Values set in the layer replace the current ones
+    pub fn apply(&mut self, layer: ConfigLayer) -> Result<(), ConfigError> {
+        if let Some(provider) = layer.llm.provider {
+            self.llm_provider = provider;
+        }
+        if let Some(model) = layer.llm.model {
+            self.llm_model = model;
+        }
+        if let Some(temperature) = layer.llm.temperature {
+            self.llm_temperature = temperature;
+        }
+        if let Some(max_attempts) = layer.llm.max_attempts {
+            self.retry_policy.max_attempts = max_attempts;
+        }
+        if let Some(is_structured_output) = layer.llm.structured_output {
+            self.is_structured_output = is_structured_output;
+        }
+        if let Some(stream_output) = layer.llm.stream_output {
+            self.stream_output = StreamOutput::from_name(&stream_output).ok_or_else(|| {
+                ConfigError::Invalid(format!(
+                    "stream_output {:?}, expected \"live\", \"progress\" or \"off\"",
+                    stream_output
+                ))
+            })?;
+        }
+        if let Some(is_dashboard) = layer.ui.dashboard {
+            self.is_dashboard = is_dashboard;
+        }
+        if let Some(is_cache_enabled) = layer.cache.enabled {
+            self.is_cache_enabled = is_cache_enabled;
+        }
+        if let Some(is_replay_only) = layer.cache.replay_only {
+            self.is_replay_only = is_replay_only;
+        }
+        if let Some(ttl_secs) = layer.cache.ttl_secs {
+            self.cache_ttl = Duration::from_secs(ttl_secs);
+        }
+        if let Some(cache_dir) = layer.cache.dir {
+            self.cache_dir = cache_dir;
+        }
+        if let Some(approval_policy) = layer.security.approval_policy {
+            self.approval_policy =
+                ApprovalPolicy::from_name(&approval_policy).ok_or_else(|| {
+                    ConfigError::Invalid(format!(
+                        "approval_policy {:?}, expected \"manual\", \"strict\" or \"auto\"",
+                        approval_policy
+                    ))
+                })?;
+        }
+        if let Some(is_security_llm_review) = layer.security.llm_review {
+            self.is_security_llm_review = is_security_llm_review;
+        }
+        if let Some(is_unattended) = layer.security.unattended {
+            self.is_unattended = is_unattended;
+        }
+        if let Some(is_clarifying) = layer.discovery.clarify {
+            self.is_clarifying = is_clarifying;
+        }
+        if let Some(max_questions) = layer.discovery.max_questions {
+            self.max_clarifying_questions = max_questions;
+        }
+        if let Some(is_checkpointing) = layer.discovery.checkpoints {
+            self.is_checkpointing = is_checkpointing;
+        }
+        if let Some(checkpoint_path) = layer.discovery.checkpoint_file {
+            self.checkpoint_path = checkpoint_path;
+        }
+        if let Some(max_rounds) = layer.limits.max_orchestration_rounds {
+            self.max_orchestration_rounds = max_rounds;
+        }
+        if let Some(max_bug_fixes) = layer.limits.max_bug_fixes {
+            self.max_bug_fixes = max_bug_fixes;
+        }
+        if let Some(startup_secs) = layer.limits.server_startup_secs {
+            self.server_startup_delay = Duration::from_secs(startup_secs);
+        }
+        if let Some(runs_dir) = layer.runs.dir {
+            self.runs_dir = runs_dir;
+        }
+        if let Some(is_exporting_traces) = layer.runs.traces {
+            self.is_exporting_traces = is_exporting_traces;
+        }
+        if let Some(timeout_secs) = layer.http.timeout_secs {
+            self.http_timeout = Duration::from_secs(timeout_secs);
+        }
+        if let Some(proxy) = layer.http.proxy {
+            self.http_proxy = Some(proxy);
+        }
+        if let Some(prompts_dir) = layer.prompts.dir {
+            self.prompts_dir = prompts_dir;
+        }
+        if let Some(project_prompts_dir) = layer.prompts.project_dir {
+            self.project_prompts_dir = project_prompts_dir;
+        }
+        if let Some(prompt_version) = layer.prompts.version {
+            self.prompt_version = Some(prompt_version);
+        }
+        merge_overrides(&mut self.agent_overrides, layer.agents);
+        merge_overrides(&mut self.ai_function_overrides, layer.ai_functions);
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |message: String| Err(ConfigError::Invalid(message));
+        let temperatures = std::iter::once(("llm", self.llm_temperature)).chain(
+            self.agent_overrides
+                .iter()
+                .chain(&self.ai_function_overrides)
+                .filter_map(|(name, model_override)| {
+                    model_override
+                        .temperature
+                        .map(|temperature| (name.as_str(), temperature))
+                }),
+        );
+        for (name, temperature) in temperatures {
+            if !(0.0..=2.0).contains(&temperature) {
+                return invalid(format!(
+                    "temperature of {} is {}, expected between 0 and 2",
+                    name, temperature
+                ));
+            }
+        }
+        let models = std::iter::once(("llm", Some(&self.llm_model))).chain(
+            self.agent_overrides
+                .iter()
+                .chain(&self.ai_function_overrides)
+                .map(|(name, model_override)| (name.as_str(), model_override.model.as_ref())),
+        );
+        for (name, model) in models {
+            if model.is_some_and(|model| model.trim().is_empty()) {
+                return invalid(format!("model of {} is empty", name));
+            }
+        }
+
+        let max_tokens = self
+            .agent_overrides
+            .iter()
+            .chain(&self.ai_function_overrides)
+            .filter_map(|(name, model_override)| {
+                model_override
+                    .max_tokens
+                    .map(|max_tokens| (name, max_tokens))
+            });
+        for (name, max_tokens) in max_tokens {
+            if max_tokens == 0 {
+                return invalid(format!("max_tokens of {} must be at least 1", name));
+            }
+        }
+
+        if self.retry_policy.max_attempts == 0 {
+            return invalid("max_attempts must be at least 1".to_string());
+        }
+        if self.max_orchestration_rounds == 0 {
+            return invalid("max_orchestration_rounds must be at least 1".to_string());
+        }
+        if self.http_timeout.is_zero() {
+            return invalid("http timeout_secs must be at least 1".to_string());
+        }
+        if self.is_replay_only && !self.is_cache_enabled {
+            return invalid("replay_only needs the cache, remove --no-cache".to_string());
+        }
+        if let Some(version) = &self.prompt_version {
+            if version.is_empty() || version.contains(['/', '\\']) || version.starts_with('.') {
+                return invalid(format!("prompt version {:?} is not a dir name", version));
+            }
+        }
+        Ok(())
+    }
+
+    // runs/run-<unix seconds>, named when the run starts
+    pub fn new_run_dir(&self) -> PathBuf {
+        let started_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        self.runs_dir.join(format!("run-{}", started_secs))
+    }
+
+    // Templates of the prompt version first, then the project ones before the shared library
+    pub fn prompt_dirs(&self) -> Vec<PathBuf> {
+        let dirs = [&self.project_prompts_dir, &self.prompts_dir];
+        let versioned = self
+            .prompt_version
+            .iter()
+            .flat_map(|version| dirs.map(|dir| dir.join(version)));
+        versioned.chain(dirs.map(PathBuf::clone)).collect()
+    }
+
+    // Routing: a configured ai_function route wins over the agent override, which wins over
+    // the built-in ai_function route, which wins over [llm]
+    pub fn model_for(&self, agent_position: &str, function_name: &str) -> ModelSettings {
+        let mut settings = ModelSettings {
+            provider: self.llm_provider,
+            model: self.llm_model.clone(),
+            temperature: self.llm_temperature,
+            max_tokens: None,
+            is_structured_output: self.is_structured_output,
+            is_cache_bypassed: false,
+        };
+        let overrides = [
+            self.ai_function_defaults.get(function_name),
+            self.agent_overrides.get(agent_position),
+            self.ai_function_overrides.get(function_name),
+        ];
+        for model_override in overrides.into_iter().flatten() {
+            if let Some(provider) = model_override.provider {
+                settings.provider = provider;
+            }
+            if let Some(model) = &model_override.model {
+                settings.model = model.clone();
+            }
+            if let Some(temperature) = model_override.temperature {
+                settings.temperature = temperature;
+            }
+            if let Some(max_tokens) = model_override.max_tokens {
+                settings.max_tokens = Some(max_tokens);
+            }
+            if let Some(is_structured_output) = model_override.structured_output {
+                settings.is_structured_output = is_structured_output;
+            }
+        }
+        settings
+    }
+}
+
+fn merge_overrides(
+    overrides: &mut HashMap<String, ModelOverride>,
+    layer_overrides: HashMap<String, ModelOverride>,
+) {
+    for (name, layer_override) in layer_overrides {
+        let model_override = overrides.entry(name).or_default();
+        if layer_override.provider.is_some() {
+            model_override.provider = layer_override.provider;
+        }
+        if layer_override.model.is_some() {
+            model_override.model = layer_override.model;
+        }
+        if layer_override.temperature.is_some() {
+            model_override.temperature = layer_override.temperature;
+        }
+        if layer_override.max_tokens.is_some() {
+            model_override.max_tokens = layer_override.max_tokens;
+        }
+        if layer_override.structured_output.is_some() {
+            model_override.structured_output = layer_override.structured_output;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(content: &str) -> ConfigLayer {
+        ConfigLayer::from_toml(Path::new(PROJECT_CONFIG_FILE), content).unwrap()
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut config = AppConfig::default();
+        config
+            .apply(layer(
+                r#"
+                [llm]
+                model = "gpt-4-turbo"
+                temperature = 0.3
+
+                [limits]
+                max_bug_fixes = 4
+
+                [agents."Backend Developer"]
+                temperature = 0.2
+                "#,
+            ))
+            .unwrap();
+        config
+            .apply(layer(
+                r#"
+                [security]
+                approval_policy = "strict"
+
+                [agents."Backend Developer"]
+                model = "gpt-4o"
+
+                [ai_functions.convert_user_input_to_goal]
+                model = "gpt-3.5-turbo"
+                "#,
+            ))
+            .unwrap();
+        config
+            .apply(ConfigLayer::from_args(&args(&["--no-cache", "--temperature=0.5"])).unwrap())
+            .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.llm_model, "gpt-4-turbo");
+        assert_eq!(config.llm_temperature, 0.5);
+        assert_eq!(config.max_bug_fixes, 4);
+        assert_eq!(config.approval_policy, ApprovalPolicy::Strict);
+        assert!(!config.is_cache_enabled);
+        assert_eq!(
+            config.agent_overrides["Backend Developer"],
+            ModelOverride {
+                model: Some("gpt-4o".to_string()),
+                temperature: Some(0.2),
+                ..ModelOverride::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_prompt_version_dirs_come_first() {
+        let mut config = AppConfig {
+            prompts_dir: PathBuf::from("library"),
+            ..AppConfig::default()
+        };
+        assert_eq!(
+            config.prompt_dirs(),
+            vec![PathBuf::from("prompts"), PathBuf::from("library")]
+        );
+
+        config
+            .apply(
+                ConfigLayer::from_args(&args(&["--prompt-version", "v2", "--recorded"])).unwrap(),
+            )
+            .unwrap();
+        config.validate().unwrap();
+        assert!(config.is_replay_only);
+        assert_eq!(
+            config.prompt_dirs(),
+            vec![
+                PathBuf::from("prompts/v2"),
+                PathBuf::from("library/v2"),
+                PathBuf::from("prompts"),
+                PathBuf::from("library"),
+            ]
+        );
+
+        config.prompt_version = Some("../v2".to_string());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_model_for_prefers_ai_function_then_agent() {
+        let mut config = AppConfig::default();
+        config
+            .apply(layer(
+                r#"
+                [agents."Project Manager"]
+                model = "gpt-4o-mini"
+                temperature = 0.0
+
+                [ai_functions.print_backend_webserver_code]
+                model = "gpt-4o"
+                "#,
+            ))
+            .unwrap();
+
+        let goal = config.model_for("Project Manager", "convert_user_input_to_goal");
+        assert_eq!(
+            (goal.model.as_str(), goal.temperature),
+            ("gpt-4o-mini", 0.0)
+        );
+        let code = config.model_for("Backend Developer", "print_backend_webserver_code");
+        assert_eq!((code.model.as_str(), code.temperature), ("gpt-4o", 0.1));
+        assert_eq!(code.max_tokens, None);
+        let scope = config.model_for("Solutions Architect", "print_project_scope");
+        assert_eq!(
+            (scope.model.as_str(), scope.max_tokens),
+            (SMALL_MODEL, Some(SMALL_MODEL_MAX_TOKENS))
+        );
+        let tests = config.model_for("Backend Developer", "print_improved_webserver_code");
+        assert_eq!(tests.model, "gpt-4");
+    }
+
+    #[test]
+    fn test_agent_override_beats_built_in_routes() {
+        let mut config = AppConfig::default();
+        config
+            .apply(layer(
+                r#"
+                [agents."Solutions Architect"]
+                model = "gpt-4o"
+
+                [ai_functions.print_site_urls]
+                max_tokens = 512
+                "#,
+            ))
+            .unwrap();
+
+        let scope = config.model_for("Solutions Architect", "print_project_scope");
+        assert_eq!(
+            (scope.model.as_str(), scope.max_tokens),
+            ("gpt-4o", Some(SMALL_MODEL_MAX_TOKENS))
+        );
+        let urls = config.model_for("Solutions Architect", "print_site_urls");
+        assert_eq!(
+            (urls.model.as_str(), urls.max_tokens),
+            ("gpt-4o", Some(512))
+        );
+        // Other agents keep the built-in route
+        let goal = config.model_for("Project Manager", "print_project_scope");
+        assert_eq!(goal.model, SMALL_MODEL);
+    }
+
+    #[test]
+    fn test_ai_function_routes_are_merged_per_field() {
+        let mut config = AppConfig::default();
+        config
+            .apply(layer(
+                r#"
+                [llm]
+                model = "gpt-4o"
+
+                [ai_functions.print_site_urls]
+                provider = "openai"
+                max_tokens = 256
+
+                [ai_functions.print_project_scope]
+                structured_output = false
+                "#,
+            ))
+            .unwrap();
+        config.validate().unwrap();
+
+        let urls = config.model_for("Solutions Architect", "print_site_urls");
+        assert_eq!(
+            urls,
+            ModelSettings {
+                provider: LlmProvider::OpenAi,
+                model: SMALL_MODEL.to_string(),
+                temperature: DEFAULT_LLM_TEMPERATURE,
+                max_tokens: Some(256),
+                is_structured_output: true,
+                is_cache_bypassed: false,
+            }
+        );
+        let code = config.model_for("Backend Developer", "print_fixed_code");
+        assert_eq!((code.model.as_str(), code.max_tokens), ("gpt-4o", None));
+        let scope = config.model_for("Solutions Architect", "print_project_scope");
+        assert!(!scope.uses_tool_calling());
+
+        let provider_err = ConfigLayer::from_toml(
+            Path::new(PROJECT_CONFIG_FILE),
+            "[ai_functions.print_site_urls]\nprovider = \"anthropic\"",
+        )
+        .unwrap_err();
+        assert!(provider_err
+            .to_string()
+            .contains("unknown variant `anthropic`"));
+
+        config
+            .apply(layer("[agents.\"Project Manager\"]\nmax_tokens = 0"))
+            .unwrap();
+        assert_eq!(
+            config.validate().unwrap_err().to_string(),
+            "invalid configuration: max_tokens of Project Manager must be at least 1"
+        );
+    }
+
+    #[test]
+    fn test_invalid_configuration_is_reported() {
+        let unknown_key =
+            ConfigLayer::from_toml(Path::new(PROJECT_CONFIG_FILE), "[llm]\nmodle = \"gpt-4\"")
+                .unwrap_err();
+        assert!(unknown_key.to_string().contains("unknown field `modle`"));
+        assert!(unknown_key.to_string().contains(PROJECT_CONFIG_FILE));
+
+        let mut config = AppConfig::default();
+        let policy_err = config
+            .apply(layer("[security]\napproval_policy = \"yolo\""))
+            .unwrap_err();
+        assert!(policy_err.to_string().contains("approval_policy \"yolo\""));
+
+        config
+            .apply(layer(
+                "[ai_functions.print_project_scope]\ntemperature = 3.0",
+            ))
+            .unwrap();
+        let temperature_err = config.validate().unwrap_err();
+        assert_eq!(
+            temperature_err.to_string(),
+            "invalid configuration: temperature of print_project_scope is 3, expected between 0 and 2"
+        );
+
+        let cli_err = ConfigLayer::from_args(&args(&["--model"])).unwrap_err();
+        assert_eq!(
+            cli_err.to_string(),
+            "invalid command line: --model needs a value"
+        );
+        assert!(ConfigLayer::from_args(&args(&["--fast"])).is_err());
+        assert_eq!(
+            project_config_path(&args(&["--config", "custom.toml"])),
+            PathBuf::from("custom.toml")
+        );
+    }
+
+    #[test]
+    fn test_example_config_file_is_valid() {
+        let example = include_str!("../../../auto_gippity.example.toml");
+        let mut config = AppConfig::default();
+        config
+            .apply(ConfigLayer::from_toml(Path::new("auto_gippity.example.toml"), example).unwrap())
+            .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config, {
+            let mut expected = AppConfig::default();
+            expected.agent_overrides.insert(
+                "Backend Developer".to_string(),
+                ModelOverride {
+                    model: Some("gpt-4".to_string()),
+                    ..ModelOverride::default()
+                },
+            );
+            expected.ai_function_overrides.insert(
+                "convert_user_input_to_goal".to_string(),
+                ModelOverride {
+                    temperature: Some(0.0),
+                    ..ModelOverride::default()
+                },
+            );
+            expected.ai_function_overrides.insert(
+                "print_backend_webserver_code".to_string(),
+                ModelOverride {
+                    provider: Some(LlmProvider::OpenAi),
+                    model: Some("gpt-4".to_string()),
+                    max_tokens: Some(4096),
+                    ..ModelOverride::default()
+                },
+            );
+            expected
+        });
+    }
+}
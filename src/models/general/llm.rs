@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Message {
     pub role: String,
     pub content: String,
@@ -10,19 +10,80 @@ pub struct ChatCompletion {
     pub model: String,
     pub messages: Vec<Message>,
     pub temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<Tool>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+    pub stream: bool,
+}
+
+// A function the model can call, its arguments follow the JSON Schema in parameters
+#[derive(Debug, Serialize, Clone)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Tool {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolChoiceFunction {
+    pub name: String,
+}
+
+// Forces the call of one function instead of a text answer
+#[derive(Debug, Serialize, Clone)]
+pub struct ToolChoice {
+    #[serde(rename = "type")]
+    pub tool_type: String,
+    pub function: ToolChoiceFunction,
 }
 
+// Streamed responses arrive as chunks, each holding the next piece of the message
 #[derive(Debug, Deserialize)]
-pub struct APIMessage {
-    pub content: String,
+pub struct APIDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Option<Vec<APIToolCallDelta>>,
+}
+
+// The arguments of a function call stream in pieces, like the content
+#[derive(Debug, Deserialize)]
+pub struct APIToolCallDelta {
+    #[serde(default)]
+    pub function: Option<APIFunctionCallDelta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIFunctionCallDelta {
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChoice {
+    pub delta: APIDelta,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct APIStreamChunk {
+    pub choices: Vec<APIStreamChoice>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct APIChoice {
-    pub message: APIMessage,
+pub struct APIErrorDetail {
+    pub message: String,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct APIResponse {
-    pub choices: Vec<APIChoice>,
+pub struct APIErrorResponse {
+    pub error: APIErrorDetail,
 }
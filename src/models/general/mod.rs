@@ -1 +1,4 @@
+pub mod app_context;
+pub mod config;
 pub mod llm;
+pub mod run_metrics;
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::models::general::llm::Message;
+
+// Counters of one run, read by the run summary and the eval report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub llm_calls: usize,
+    // Prompts and responses, cached or not, so two prompt versions compare the same way
+    pub estimated_tokens: usize,
+    pub builds: usize,
+    pub successful_builds: usize,
+    pub fix_rounds: usize,
+    pub endpoint_checks: usize,
+    pub endpoint_passes: usize,
+}
+
+// Shared by every agent of the run through the app context
+#[derive(Debug, Default)]
+pub struct RunMetrics {
+    llm_calls: AtomicUsize,
+    estimated_tokens: AtomicUsize,
+    builds: AtomicUsize,
+    successful_builds: AtomicUsize,
+    fix_rounds: AtomicUsize,
+    endpoint_checks: AtomicUsize,
+    endpoint_passes: AtomicUsize,
+}
+
+// Rough estimate, about 4 characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len() / 4
+}
+
+impl RunMetrics {
+    // Returns the estimated tokens of this call
+    pub fn record_llm_call(&self, messages: &[Message], response: &str) -> usize {
+        let prompt_tokens: usize = messages
+            .iter()
+            .map(|message| estimate_tokens(&message.content))
+            .sum();
+        let call_tokens = prompt_tokens + estimate_tokens(response);
+        self.llm_calls.fetch_add(1, Ordering::Relaxed);
+        self.estimated_tokens
+            .fetch_add(call_tokens, Ordering::Relaxed);
+        call_tokens
+    }
+
+    pub fn record_build(&self, is_success: bool) {
+        self.builds.fetch_add(1, Ordering::Relaxed);
+        if is_success {
+            self.successful_builds.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    // One call to the LLM to fix a build or a migration
+    pub fn record_fix_round(&self) {
+        self.fix_rounds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_endpoint_check(&self, is_passed: bool) {
+        self.endpoint_checks.fetch_add(1, Ordering::Relaxed);
+        if is_passed {
+            self.endpoint_passes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            llm_calls: self.llm_calls.load(Ordering::Relaxed),
+            estimated_tokens: self.estimated_tokens.load(Ordering::Relaxed),
+            builds: self.builds.load(Ordering::Relaxed),
+            successful_builds: self.successful_builds.load(Ordering::Relaxed),
+            fix_rounds: self.fix_rounds.load(Ordering::Relaxed),
+            endpoint_checks: self.endpoint_checks.load(Ordering::Relaxed),
+            endpoint_passes: self.endpoint_passes.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// Authentication module injected by the Backend Developer agent.
+// It is vetted code: the generated backend uses it as-is and never rewrites it.
+//
+// Usage in main.rs:
+//   mod auth;
+//   let auth_state = web::Data::new(auth::AuthState::new());
+//   App::new()
+//       .app_data(auth_state.clone())
+//       .configure(auth::configure)
+//       .route("/profile", web::get().to(profile)) // async fn profile(user: auth::AuthenticatedUser)
+//       .service(web::scope("/private").wrap(from_fn(auth::require_login)) ...)
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::ErrorUnauthorized;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, FromRequest, HttpRequest, HttpResponse};
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::sync::Mutex;
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub token: String,
+}
+
+// Users with their argon2 password hash, and the active session tokens
+pub struct AuthState {
+    users: Mutex<HashMap<String, String>>,
+    sessions: Mutex<HashMap<String, String>>,
+}
+
+impl AuthState {
+    pub fn new() -> Self {
+        Self {
+            users: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn username_for_token(&self, token: &str) -> Option<String> {
+        self.sessions.lock().unwrap().get(token).cloned()
+    }
+}
+
+impl Default for AuthState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Registers POST /auth/signup, POST /auth/login and POST /auth/logout
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/auth/signup", web::post().to(signup))
+        .route("/auth/login", web::post().to(login))
+        .route("/auth/logout", web::post().to(logout));
+}
+
+fn hash_password(password: &str) -> Option<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .ok()
+        .map(|hash| hash.to_string())
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn new_session_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+async fn signup(state: web::Data<AuthState>, credentials: web::Json<Credentials>) -> HttpResponse {
+    if credentials.username.is_empty() || credentials.password.len() < 8 {
+        return HttpResponse::BadRequest()
+            .body("username is required and password needs at least 8 characters");
+    }
+
+    let mut users = state.users.lock().unwrap();
+    if users.contains_key(&credentials.username) {
+        return HttpResponse::Conflict().body("username already taken");
+    }
+    match hash_password(&credentials.password) {
+        Some(password_hash) => {
+            users.insert(credentials.username.clone(), password_hash);
+            HttpResponse::Created().finish()
+        }
+        None => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+async fn login(state: web::Data<AuthState>, credentials: web::Json<Credentials>) -> HttpResponse {
+    let password_hash = state.users.lock().unwrap().get(&credentials.username).cloned();
+    match password_hash {
+        Some(password_hash) if verify_password(&credentials.password, &password_hash) => {
+            let token = new_session_token();
+            state
+                .sessions
+                .lock()
+                .unwrap()
+                .insert(token.clone(), credentials.username.clone());
+            HttpResponse::Ok().json(TokenResponse { token })
+        }
+        _ => HttpResponse::Unauthorized().body("invalid username or password"),
+    }
+}
+
+async fn logout(state: web::Data<AuthState>, req: HttpRequest) -> HttpResponse {
+    match bearer_token(&req) {
+        Some(token) if state.sessions.lock().unwrap().remove(&token).is_some() => {
+            HttpResponse::Ok().finish()
+        }
+        _ => HttpResponse::Unauthorized().finish(),
+    }
+}
+
+// Extractor: add `user: auth::AuthenticatedUser` to a handler to make its route require login
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub username: String,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let username = req
+            .app_data::<web::Data<AuthState>>()
+            .zip(bearer_token(req))
+            .and_then(|(state, token)| state.username_for_token(&token));
+        ready(match username {
+            Some(username) => Ok(AuthenticatedUser { username }),
+            None => Err(ErrorUnauthorized("login required")),
+        })
+    }
+}
+
+// Middleware: wrap a scope with `from_fn(auth::require_login)` to protect all of its routes
+pub async fn require_login(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let is_logged_in = req
+        .app_data::<web::Data<AuthState>>()
+        .zip(bearer_token(req.request()))
+        .and_then(|(state, token)| state.username_for_token(&token))
+        .is_some();
+    if !is_logged_in {
+        return Err(ErrorUnauthorized("login required"));
+    }
+    next.call(req).await
+}